@@ -1,5 +1,5 @@
 #!/bin/bash
 DEBUG="target/debug/"
 
-./"${DEBUG}"ccr "$1"
+./"${DEBUG}"ccr -S -o "${DEBUG}"tmp.s "$1"
 echo "tmp.s is created at $DEBUG"
@@ -2,6 +2,6 @@
 CCR_DIR="$(cd $(dirname $0); pwd)/"
 DEBUG="${CCR_DIR}target/debug/"
 
-"${DEBUG}"ccr -q ${CCR_DIR}test/link-test/test.c #"$1"
+"${DEBUG}"ccr -q -S -o "${DEBUG}"tmp.s ${CCR_DIR}test/link-test/test.c #"$1"
 cc "${CCR_DIR}"test/link-test/link.c "${DEBUG}"tmp.s -o "${DEBUG}"tmp
 "${DEBUG}"tmp
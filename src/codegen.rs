@@ -1,237 +1,593 @@
+use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{self, BufWriter, Write};
 
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::parse::GInit;
 use crate::parse::NodeKind;
+use crate::parse::Type;
 use crate::parse::AST;
 
-const ARGREG: [&'static str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const ARGREG: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const ARGREG32: [&str; 6] = ["edi", "esi", "edx", "ecx", "r8d", "r9d"];
+const ARGREG16: [&str; 6] = ["di", "si", "dx", "cx", "r8w", "r9w"];
+const ARGREG8: [&str; 6] = ["dil", "sil", "dl", "cl", "r8b", "r9b"];
 
 #[derive(Debug)]
 struct CodeGenerator {
     ast_list: Vec<AST>,
     label_cnt: usize,
+    strings: Vec<Vec<u8>>, // string literals placed in .rodata as .LC<index>
     f: BufWriter<File>,
 }
 
-// ASTの配列からアセンブリ全体を生成する
-pub fn codegen(vec: Vec<AST>, fname: &str) {
-    // set the currrent directory
-    let fpath = std::env::current_exe()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .join(fname);
+// ASTの配列からアセンブリ全体を生成し、fに書き出す
+pub fn codegen(vec: Vec<AST>, f: File) -> Result<(), Diagnostic> {
     let mut gen = CodeGenerator {
         ast_list: vec,
         label_cnt: 0,
-        f: BufWriter::new(File::create(&fpath).unwrap()),
+        strings: Vec::new(),
+        f: BufWriter::new(f),
     };
 
-    gen.output(".intel_syntax noprefix");
-    gen.output(".global main");
+    gen.output(".intel_syntax noprefix")?;
 
     // vecの各要素(stmt)からアセンブリを生成する。
     for elm in gen.ast_list.clone() {
-        gen.gen_no_ret(elm);
+        gen.gen_no_ret(elm)?;
     }
+
+    gen.gen_rodata()?;
+
+    // the generated code never needs an executable stack
+    gen.output(".section .note.GNU-stack,\"\",@progbits")?;
+    gen.f.flush().map_err(write_error)?;
+    Ok(())
+}
+
+// error at the node (Nil has no location)
+fn error(code: &'static str, msg: &str, ast: &AST) -> Diagnostic {
+    let span = match ast {
+        AST::Nil => None,
+        _ => Some(ast.span()),
+    };
+    Diagnostic::new(DiagnosticKind::Codegen, code, msg, span)
+}
+
+// the assembly couldn't be written (ex: the disk is full)
+fn write_error(why: io::Error) -> Diagnostic {
+    let msg = format!("couldn't write the assembly: {}", why);
+    Diagnostic::new(DiagnosticKind::Codegen, "write-failed", &msg, None)
+}
+
+// bug of ccr, not of the source (ex: a node the code generator doesn't know)
+fn internal_error(msg: &str, ast: &AST) -> Diagnostic {
+    let span = match ast {
+        AST::Nil => None,
+        _ => Some(ast.span()),
+    };
+    Diagnostic::new(DiagnosticKind::Internal, "internal-error", msg, span)
 }
 
 fn is_nil(ast: AST) -> bool {
-    match ast {
-        AST::Nil => true,
-        _ => false,
-    }
+    matches!(ast, AST::Nil)
 }
 
-fn is_var(ast: AST) -> bool {
-    match ast {
-        AST::Node {
-            kind: NodeKind::Var { .. },
-        } => true,
-        _ => false,
-    }
+// unsigned integers and pointers are compared without the sign
+fn is_unsigned(ast: &AST) -> bool {
+    let ty = ast.ty();
+    ty.is_unsigned() || matches!(ty.decay(), Type::Ptr(_))
 }
 
 impl CodeGenerator {
     // write str to the file
-    fn output(&mut self, s: &str) {
-        writeln!(self.f, "{}", s).unwrap();
+    fn output(&mut self, s: &str) -> Result<(), Diagnostic> {
+        writeln!(self.f, "{}", s).map_err(write_error)
+    }
+
+    // string literals with the terminating NUL
+    fn gen_rodata(&mut self) -> Result<(), Diagnostic> {
+        if self.strings.is_empty() {
+            return Ok(());
+        }
+        self.output("    .section .rodata")?;
+        for (i, s) in self.strings.clone().iter().enumerate() {
+            let bytes: Vec<String> = s.iter().chain(&[0]).map(|b| b.to_string()).collect();
+            self.output(&format!(".LC{}:", i))?;
+            self.output(&format!("    .byte {}", bytes.join(", ")))?;
+        }
+        Ok(())
+    }
+
+    // load the value at the address in rax into rax
+    fn load(&mut self, ty: &Type) -> Result<(), Diagnostic> {
+        match ty {
+            // the address itself is the value of an array, a struct and a function
+            Type::Array(..) | Type::Struct(_) | Type::Func { .. } => Ok(()),
+            // signed values are sign-extended, and unsigned values are zero-extended
+            Type::Char => self.output("    movsx rax, byte ptr [rax]"),
+            Type::Short => self.output("    movsx rax, word ptr [rax]"),
+            Type::Int => self.output("    movsxd rax, dword ptr [rax]"),
+            Type::UChar => self.output("    movzx eax, byte ptr [rax]"),
+            Type::UShort => self.output("    movzx eax, word ptr [rax]"),
+            Type::UInt => self.output("    mov eax, dword ptr [rax]"),
+            _ => self.output("    mov rax, [rax]"),
+        }
+    }
+
+    // store rdi to the address in rax
+    fn store(&mut self, ty: &Type) -> Result<(), Diagnostic> {
+        // rdi is the address of the struct, which is copied byte by byte
+        if let Type::Struct(_) = ty {
+            for i in 0..ty.size() {
+                self.output(&format!("    mov r8b, [rdi + {}]", i))?;
+                self.output(&format!("    mov [rax + {}], r8b", i))?;
+            }
+            return Ok(());
+        }
+        match ty.size() {
+            1 => self.output("    mov [rax], dil"),
+            2 => self.output("    mov [rax], di"),
+            4 => self.output("    mov [rax], edi"),
+            _ => self.output("    mov [rax], rdi"),
+        }
+    }
+
+    // extend the value of the type in rax to 64 bits
+    // the ABI leaves the upper bits of the narrow return values undefined
+    fn extend(&mut self, ty: &Type) -> Result<(), Diagnostic> {
+        match ty {
+            Type::Char => self.output("    movsx rax, al"),
+            Type::Short => self.output("    movsx rax, ax"),
+            Type::Int => self.output("    movsxd rax, eax"),
+            Type::UChar => self.output("    movzx eax, al"),
+            Type::UShort => self.output("    movzx eax, ax"),
+            Type::UInt => self.output("    mov eax, eax"),
+            // the other values are already 64 bits (or not values at all)
+            Type::Long
+            | Type::ULong
+            | Type::Ptr(_)
+            | Type::Void
+            | Type::Array(..)
+            | Type::Struct(_)
+            | Type::Func { .. } => Ok(()),
+        }
+    }
+
+    // evaluate all the arguments before storing them in the registers
+    // since evaluating one can clobber the registers (ex: rdx by idiv)
+    fn gen_args(&mut self, args: Vec<AST>) -> Result<(), Diagnostic> {
+        let argc = args.len();
+        for arg in args {
+            self.gen_expr(arg)?;
+        }
+        for reg in ARGREG[..argc].iter().rev() {
+            self.output(&format!("    pop {}", reg))?;
+        }
+        Ok(())
+    }
+
+    // call the function (the name or the register which has the address)
+    // and push the returned value
+    fn gen_call(&mut self, target: &str, ret_ty: &Type) -> Result<(), Diagnostic> {
+        // the ABI requires rsp to be aligned to 16 bytes at the call
+        // rsp is always a multiple of 8, so it is off by 8 or aligned
+        // al is the number of vector registers used by variadic functions
+        let label_call = format!(".Lcall{}", self.label_cnt);
+        let label_end = format!(".Lend{}", self.label_cnt);
+        self.label_cnt += 1;
+        self.output("    mov rax, rsp")?;
+        self.output("    and rax, 15")?;
+        self.output(&format!("    jnz {}", label_call))?;
+        self.output("    mov rax, 0")?;
+        self.output(&format!("    call {}", target))?;
+        self.output(&format!("    jmp {}", label_end))?;
+        self.output(&format!("{}:", label_call))?;
+        self.output("    sub rsp, 8")?;
+        self.output("    mov rax, 0")?;
+        self.output(&format!("    call {}", target))?;
+        self.output("    add rsp, 8")?;
+        self.output(&format!("{}:", label_end))?;
+        self.extend(ret_ty)?;
+        self.output("    push rax")
+    }
+
+    // global variable in .data (with an initializer) or .bss
+    fn gen_gvar(&mut self, name: &str, ty: &Type, init: Option<GInit>) -> Result<(), Diagnostic> {
+        let section = match init {
+            Some(_) => ".data",
+            None => ".bss",
+        };
+        self.output(&format!("    {}", section))?;
+        self.output(&format!(".global {}", name))?;
+        self.output(&format!("    .align {}", ty.align()))?;
+        self.output(&format!("{}:", name))?;
+        match init {
+            None => self.output(&format!("    .zero {}", ty.size())),
+            Some(GInit::Int(val)) => match ty.size() {
+                1 => self.output(&format!("    .byte {}", val as i8)),
+                2 => self.output(&format!("    .short {}", val as i16)),
+                4 => self.output(&format!("    .long {}", val as i32)),
+                _ => self.output(&format!("    .quad {}", val)),
+            },
+            Some(GInit::Str(s)) => {
+                self.output(&format!("    .quad .LC{}", self.strings.len()))?;
+                self.strings.push(s);
+                Ok(())
+            }
+            Some(GInit::Addr(name)) => self.output(&format!("    .quad {}", name)),
+        }
     }
 
     // push address of variables
-    fn gen_addr(&mut self, ast: AST) {
+    fn gen_addr(&mut self, ast: AST) -> Result<(), Diagnostic> {
         match ast {
             AST::Node {
                 kind: NodeKind::Var { offset: ofs, .. },
+                ..
+            } => {
+                self.output("    mov rax, rbp")?;
+                self.output(&format!("    sub rax, {}", ofs))?;
+                self.output("    push rax")?;
+            }
+            // global variables are addressed relative to rip
+            // the functions can be in the shared libraries, so their addresses are in the GOT
+            AST::Node {
+                kind:
+                    NodeKind::GVar {
+                        name,
+                        ty: Type::Func { .. },
+                    },
+                ..
+            } => {
+                self.output(&format!("    mov rax, [rip + {}@GOTPCREL]", name))?;
+                self.output("    push rax")?;
+            }
+            AST::Node {
+                kind: NodeKind::GVar { name, .. },
+                ..
             } => {
-                self.output("    mov rax, rbp");
-                self.output(&format!("    sub rax, {}", ofs));
-                self.output("    push rax");
+                self.output(&format!("    lea rax, [rip + {}]", name))?;
+                self.output("    push rax")?;
+            }
+            // the address of *p is p
+            AST::Node {
+                kind: NodeKind::Deref(operand),
+                ..
+            } => self.gen_expr(*operand)?,
+            // the member is at the offset from the struct
+            AST::Node {
+                kind: NodeKind::Member { base, offset, .. },
+                ..
+            } => {
+                self.gen_addr(*base)?;
+                self.output("    pop rax")?;
+                self.output(&format!("    add rax, {}", offset))?;
+                self.output("    push rax")?;
             }
             _ => {
-                panic!("non variable nodes doesn't have addresses");
+                return Err(error(
+                    "not-addressable",
+                    "non variable nodes doesn't have addresses",
+                    &ast,
+                ));
             }
         };
+        Ok(())
     }
 
     // exprからアセンブリを出力する　Nilは受け付けない
-    pub fn gen_expr(&mut self, ast: AST) {
+    pub fn gen_expr(&mut self, ast: AST) -> Result<(), Diagnostic> {
         if is_nil(ast.clone()) {
-            panic!("Nil is not expr");
+            return Err(error("expected-expression", "expected an expression", &ast));
         }
 
         let k = ast.kind().clone();
 
         match k {
             // assignment
-            // lhs is variables or deref*, rhs is expression
+            // lhs is variables or deref* (checked by sema), rhs is expression
             NodeKind::Assign(l, r) => {
+                let ty = l.ty();
                 // push the address of lhs
-                match *l.clone() {
-                    AST::Node {
-                        kind: NodeKind::Var { .. },
-                    } => self.gen_addr(*l),
-                    AST::Node {
-                        kind: NodeKind::Deref(ast),
-                    } => self.gen_expr(*ast),
-                    _ => panic!("incorrect assignment: lhs is wrong expression"),
-                }
-                self.gen_expr(*r);
-                self.output("    pop rdi"); // rhs
-                self.output("    pop rax"); // lhs
-                self.output("    mov [rax], rdi");
-                self.output("    push rdi");
+                self.gen_addr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?; // rhs
+                self.output("    pop rax")?; // lhs
+                self.store(&ty)?;
+                self.output("    push rdi")?;
             }
             // function call
             NodeKind::FuncCall {
                 name: func_name,
                 argv: args,
+                ..
             } => {
-                // Todo 関数呼び出し直前にrspを16の倍数にアラインするアセンブリをかく
-                // store argument in the registers
-                for i in 0..=5 {
-                    if args.len() > 5 - i {
-                        self.gen_expr(args[5 - i].clone());
-                        self.output("    pop rax");
-                        self.output(&format!("    mov {}, rax", ARGREG[5 - i]));
-                    }
+                if args.len() > 6 {
+                    return Err(error(
+                        "too-many-register-arguments",
+                        "the number of arguments must be < 7",
+                        &ast,
+                    ));
                 }
-
+                self.gen_args(args)?;
+                self.gen_call(&func_name, &ast.ty())?;
+            }
+            // the address of the function is evaluated before the arguments
+            NodeKind::FuncPtrCall { callee, argv: args } => {
                 if args.len() > 6 {
-                    panic!("the number of arguments must be < 7")
+                    return Err(error(
+                        "too-many-register-arguments",
+                        "the number of arguments must be < 7",
+                        &ast,
+                    ));
                 }
-
-                self.output(&format!("    call {}", func_name));
-                self.output("    push rax");
+                self.gen_expr(*callee)?;
+                self.gen_args(args)?;
+                self.output("    pop r10")?;
+                self.gen_call("r10", &ast.ty())?;
             }
             // integers
-            NodeKind::Num(i) => {
-                self.output(&format!("    push {}", i));
-            }
-            // variables
-            NodeKind::Var { .. } => {
-                self.gen_addr(ast);
-                self.output("    pop rax");
-                self.output("    mov rax, [rax]");
-                self.output("    push rax");
-            }
-            NodeKind::Deref(ast) => {
-                self.gen_expr(*ast);
-                self.output("    pop rax");
-                self.output("    mov rax, [rax]");
-                self.output("    push rax");
-            }
-            NodeKind::Addr(ast) => {
-                if is_var(*ast.clone()) {
-                    self.gen_addr(*ast);
+            NodeKind::Num(val, _) => {
+                // push takes only a sign-extended 32-bit immediate
+                if i32::try_from(val).is_ok() {
+                    self.output(&format!("    push {}", val))?;
                 } else {
-                    self.gen_expr(*ast);
+                    self.output(&format!("    mov rax, {}", val))?;
+                    self.output("    push rax")?;
                 }
             }
+            // string literals are addressed relative to rip
+            NodeKind::Str(s) => {
+                self.output(&format!("    lea rax, [rip + .LC{}]", self.strings.len()))?;
+                self.output("    push rax")?;
+                self.strings.push(s);
+            }
+            // variables
+            NodeKind::Var { ty, .. } | NodeKind::GVar { ty, .. } => {
+                self.gen_addr(ast)?;
+                self.output("    pop rax")?;
+                self.load(&ty)?;
+                self.output("    push rax")?;
+            }
+            NodeKind::Deref(operand) => {
+                self.gen_expr(*operand)?;
+                self.output("    pop rax")?;
+                self.load(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            NodeKind::Member { ty, .. } => {
+                self.gen_addr(ast)?;
+                self.output("    pop rax")?;
+                self.load(&ty)?;
+                self.output("    push rax")?;
+            }
+            NodeKind::Addr(ast) => self.gen_addr(*ast)?,
+            NodeKind::Comma(l, r) => {
+                self.gen_expr(*l)?;
+                self.output("    add rsp, 8")?;
+                self.gen_expr(*r)?;
+            }
+            NodeKind::Cast(operand) => {
+                self.gen_expr(*operand)?;
+                self.output("    pop rax")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
             // --- arithmetic operators ---
             NodeKind::Plus(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    add rax, rdi");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    add rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
             }
             NodeKind::Minus(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    sub rax, rdi");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    sub rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
             }
             NodeKind::Mul(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    imul rax, rdi");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    imul rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
             }
             NodeKind::Div(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    cqo");
-                self.output("    idiv rdi");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                if ast.ty().is_unsigned() {
+                    self.output("    mov rdx, 0")?;
+                    self.output("    div rdi")?;
+                } else {
+                    self.output("    cqo")?;
+                    self.output("    idiv rdi")?;
+                }
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            // the remainder of the division is in rdx
+            NodeKind::Mod(l, r) => {
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                if ast.ty().is_unsigned() {
+                    self.output("    mov rdx, 0")?;
+                    self.output("    div rdi")?;
+                } else {
+                    self.output("    cqo")?;
+                    self.output("    idiv rdi")?;
+                }
+                self.output("    mov rax, rdx")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            // --- bitwise operators ---
+            NodeKind::BitAnd(l, r) => {
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    and rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            NodeKind::BitOr(l, r) => {
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    or rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            NodeKind::BitXor(l, r) => {
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    xor rax, rdi")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            NodeKind::BitNot(operand) => {
+                self.gen_expr(*operand)?;
+                self.output("    pop rax")?;
+                self.output("    not rax")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            // the shift count is in cl
+            NodeKind::Shl(l, r) => {
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rcx")?;
+                self.output("    pop rax")?;
+                self.output("    shl rax, cl")?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
+            }
+            // the signed values are shifted arithmetically (the sign bit is copied)
+            NodeKind::Shr(l, r) => {
+                let shr = if ast.ty().is_unsigned() { "shr" } else { "sar" };
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rcx")?;
+                self.output("    pop rax")?;
+                self.output(&format!("    {} rax, cl", shr))?;
+                self.extend(&ast.ty())?;
+                self.output("    push rax")?;
             }
             // comparison operators
             NodeKind::Eq(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    cmp rax, rdi");
-                self.output("    sete al");
-                self.output("    movzb rax, al");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, rdi")?;
+                self.output("    sete al")?;
+                self.output("    movzb rax, al")?;
+                self.output("    push rax")?;
             }
             NodeKind::Ne(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    cmp rax, rdi");
-                self.output("    setne al");
-                self.output("    movzb rax, al");
-                self.output("    push rax");
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, rdi")?;
+                self.output("    setne al")?;
+                self.output("    movzb rax, al")?;
+                self.output("    push rax")?;
             }
             NodeKind::Lt(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    cmp rax, rdi");
-                self.output("    setl al");
-                self.output("    movzb rax, al");
-                self.output("    push rax");
+                let set = if is_unsigned(&l) { "setb" } else { "setl" };
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, rdi")?;
+                self.output(&format!("    {} al", set))?;
+                self.output("    movzb rax, al")?;
+                self.output("    push rax")?;
             }
             NodeKind::Le(l, r) => {
-                self.gen_expr(*l);
-                self.gen_expr(*r);
-                self.output("    pop rdi");
-                self.output("    pop rax");
-                self.output("    cmp rax, rdi");
-                self.output("    setle al");
-                self.output("    movzb rax, al");
-                self.output("    push rax");
-            }
-            _ => (),
+                let set = if is_unsigned(&l) { "setbe" } else { "setle" };
+                self.gen_expr(*l)?;
+                self.gen_expr(*r)?;
+                self.output("    pop rdi")?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, rdi")?;
+                self.output(&format!("    {} al", set))?;
+                self.output("    movzb rax, al")?;
+                self.output("    push rax")?;
+            }
+            // logical operators
+            // the right operand is evaluated only if the left one doesn't decide the result
+            NodeKind::LogAnd(l, r) => {
+                let label_false = format!(".Lfalse{}", self.label_cnt);
+                let label_end = format!(".Lend{}", self.label_cnt);
+                self.label_cnt += 1;
+                self.gen_expr(*l)?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, 0")?;
+                self.output(&format!("    je {}", label_false))?;
+                self.gen_expr(*r)?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, 0")?;
+                self.output(&format!("    je {}", label_false))?;
+                self.output("    push 1")?;
+                self.output(&format!("    jmp {}", label_end))?;
+                self.output(&format!("{}:", label_false))?;
+                self.output("    push 0")?;
+                self.output(&format!("{}:", label_end))?;
+            }
+            NodeKind::LogOr(l, r) => {
+                let label_true = format!(".Ltrue{}", self.label_cnt);
+                let label_end = format!(".Lend{}", self.label_cnt);
+                self.label_cnt += 1;
+                self.gen_expr(*l)?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, 0")?;
+                self.output(&format!("    jne {}", label_true))?;
+                self.gen_expr(*r)?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, 0")?;
+                self.output(&format!("    jne {}", label_true))?;
+                self.output("    push 0")?;
+                self.output(&format!("    jmp {}", label_end))?;
+                self.output(&format!("{}:", label_true))?;
+                self.output("    push 1")?;
+                self.output(&format!("{}:", label_end))?;
+            }
+            NodeKind::Not(operand) => {
+                self.gen_expr(*operand)?;
+                self.output("    pop rax")?;
+                self.output("    cmp rax, 0")?;
+                self.output("    sete al")?;
+                self.output("    movzb rax, al")?;
+                self.output("    push rax")?;
+            }
+            _ => return Err(internal_error("unexpected node in an expression", &ast)),
         };
-        return;
+        Ok(())
     }
 
     // スタックトップに結果が積まれないもの (式以外)
-    fn gen_no_ret(&mut self, ast: AST) {
+    fn gen_no_ret(&mut self, ast: AST) -> Result<(), Diagnostic> {
         if is_nil(ast.clone()) {
-            panic!("incorrect statement");
+            return Err(internal_error("unexpected empty statement", &ast));
         }
         match ast.kind() {
             NodeKind::FuncDecl {
@@ -241,38 +597,54 @@ impl CodeGenerator {
                 frame_size: func_frame_size,
                 stmts: func_stmts,
             } => {
-                self.output(&format!("{}:", func_name));
-                self.output("    push rbp");
-                self.output("    mov rbp, rsp");
+                // the parameters are passed only in the registers
+                if func_args.len() > 6 {
+                    return Err(error(
+                        "too-many-register-arguments",
+                        "the number of arguments must be < 7",
+                        &ast,
+                    ));
+                }
+                // every function is visible from other translation units
+                self.output("    .text")?;
+                self.output(&format!(".global {}", func_name))?;
+                self.output(&format!("{}:", func_name))?;
+                self.output("    push rbp")?;
+                self.output("    mov rbp, rsp")?;
                 // prepare the stack frame
-                self.output(&format!("    sub rsp, {}", func_frame_size));
+                self.output(&format!("    sub rsp, {}", func_frame_size))?;
 
-                for i in 0..6 {
-                    if func_args.len() >= i + 1 {
-                        self.output(&format!(
-                            "    mov [rbp - {}], {}",
-                            func_args[i].0, ARGREG[i]
-                        ));
-                    }
+                // store the arguments with the width of their types
+                for (i, (offset, ty)) in func_args.iter().enumerate() {
+                    let reg = match ty.size() {
+                        1 => ARGREG8[i],
+                        2 => ARGREG16[i],
+                        4 => ARGREG32[i],
+                        _ => ARGREG[i],
+                    };
+                    self.output(&format!("    mov [rbp - {}], {}", offset, reg))?;
                 }
-                for elm in *func_stmts.clone() {
-                    self.gen_no_ret(elm);
+                for elm in func_stmts {
+                    self.gen_no_ret(elm)?;
                     // one value remains on the stack top as the result of evaluating expression
                 }
 
-                // ToDo ret_typeでどうこうする
-
-                return;
+                // falling off the end returns like "return;" (main returns 0 as C99 requires)
+                if func_name == "main" {
+                    self.output("    mov rax, 0")?;
+                }
+                self.output("    mov rsp, rbp")?;
+                self.output("    pop rbp")?;
+                self.output("    ret")?;
             }
             NodeKind::Return(ast) => {
                 if !is_nil(*ast.clone()) {
-                    self.gen_expr(*ast);
-                    self.output("    pop rax");
+                    self.gen_expr(*ast)?;
+                    self.output("    pop rax")?;
                 }
-                self.output("    mov rsp, rbp");
-                self.output("    pop rbp");
-                self.output("    ret");
-                return;
+                self.output("    mov rsp, rbp")?;
+                self.output("    pop rbp")?;
+                self.output("    ret")?;
             }
             NodeKind::If {
                 cond: c,
@@ -284,13 +656,12 @@ impl CodeGenerator {
                     let label = format!(".Lend{}", self.label_cnt);
                     self.label_cnt += 1;
 
-                    self.gen_expr(*c);
-                    self.output("    pop rax");
-                    self.output("    cmp rax, 0");
-                    self.output(&format!("    je {}", label));
-                    self.gen_no_ret(*t);
-                    self.output(&format!("{}:", label));
-                    return;
+                    self.gen_expr(*c)?;
+                    self.output("    pop rax")?;
+                    self.output("    cmp rax, 0")?;
+                    self.output(&format!("    je {}", label))?;
+                    self.gen_no_ret(*t)?;
+                    self.output(&format!("{}:", label))?;
                 }
                 // if-else
                 else {
@@ -299,16 +670,15 @@ impl CodeGenerator {
                     let label_end = format!(".Lelse{}", self.label_cnt);
                     self.label_cnt += 1;
 
-                    self.gen_expr(*c);
-                    self.output("    pop rax");
-                    self.output("    cmp rax, 0");
-                    self.output(&format!("    je {}", label_else));
-                    self.gen_no_ret(*t);
-                    self.output(&format!("    jmp {}", label_end));
-                    self.output(&format!("{}:", label_else));
-                    self.gen_no_ret(*e);
-                    self.output(&format!("{}:", label_end));
-                    return;
+                    self.gen_expr(*c)?;
+                    self.output("    pop rax")?;
+                    self.output("    cmp rax, 0")?;
+                    self.output(&format!("    je {}", label_else))?;
+                    self.gen_no_ret(*t)?;
+                    self.output(&format!("    jmp {}", label_end))?;
+                    self.output(&format!("{}:", label_else))?;
+                    self.gen_no_ret(*e)?;
+                    self.output(&format!("{}:", label_end))?;
                 }
             }
             NodeKind::For {
@@ -323,34 +693,39 @@ impl CodeGenerator {
                 self.label_cnt += 1;
                 // Nilを許容
                 if !is_nil(*expr_a.clone()) {
-                    self.gen_no_ret(*expr_a);
+                    self.gen_no_ret(*expr_a)?;
                 }
-                self.output(&format!("{}:", label_begin));
+                self.output(&format!("{}:", label_begin))?;
                 if !is_nil(*expr_b.clone()) {
-                    self.gen_expr(*expr_b);
-                    self.output("    pop rax");
-                    self.output("    cmp rax, 0");
-                    self.output(&format!("    je {}", label_end));
+                    self.gen_expr(*expr_b)?;
+                    self.output("    pop rax")?;
+                    self.output("    cmp rax, 0")?;
+                    self.output(&format!("    je {}", label_end))?;
                 }
-                self.gen_no_ret(*p);
+                self.gen_no_ret(*p)?;
                 if !is_nil(*expr_c.clone()) {
-                    self.gen_expr(*expr_c);
+                    self.gen_expr(*expr_c)?;
+                    // discard the result so that the stack doesn't grow every iteration
+                    self.output("    add rsp, 8")?;
                 }
-                self.output(&format!("    jmp {}", label_begin));
-                self.output(&format!("{}:", label_end));
-                return;
+                self.output(&format!("    jmp {}", label_begin))?;
+                self.output(&format!("{}:", label_end))?;
             }
+            NodeKind::GVarDecl { name, ty, init } => self.gen_gvar(&name, &ty, init)?,
             NodeKind::Block(vec) => {
-                for ast in *vec {
-                    self.gen_no_ret(ast);
+                for ast in vec {
+                    self.gen_no_ret(ast)?;
                 }
-                return;
             }
-            // expression statement
+            // expression statement (Nil for the empty statement ";")
             NodeKind::ExprStmt(expr) => {
-                self.gen_expr(*expr);
+                if !is_nil(*expr.clone()) {
+                    self.gen_expr(*expr)?;
+                    self.output("    add rsp, 8")?;
+                }
             }
-            _ => panic!("incorrect statement"),
-        };
+            _ => return Err(internal_error("unexpected node in a statement", &ast)),
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,459 @@
+use std::fmt;
+use std::fmt::Write;
+
+use crate::source::{FileId, SourceMap, Span};
+
+#[test]
+fn test_describe_fixit() {
+    let mut map = SourceMap::new();
+    let id = map.add_file("test.c", String::from("int x = 1;;"));
+    let fixit = |start, end, replacement: &str| FixIt {
+        span: map.file(id).span(start, end),
+        replacement: String::from(replacement),
+    };
+    assert_eq!(describe_fixit(&map, &fixit(10, 10, ";")), "insert ';'");
+    assert_eq!(describe_fixit(&map, &fixit(10, 11, "")), "remove ';'");
+    assert_eq!(
+        describe_fixit(&map, &fixit(8, 9, "2")),
+        "replace '1' with '2'"
+    );
+}
+
+// ANSI escape sequences used when the output is colored
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const MAGENTA: &str = "\x1b[1;35m";
+const CYAN: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+// where in the pipeline the error was detected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticKind {
+    Lex,      // invalid characters or literals
+    Syntax,   // unexpected tokens
+    Semantic, // undefined names, invalid expressions...
+    Codegen,  // constructs the code generator can't handle
+    Internal, // bugs of ccr itself (ex: a node a pass doesn't expect)
+    Driver,   // bad options, missing files, failed commands...
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DiagnosticKind::Lex => "lexical",
+            DiagnosticKind::Syntax => "syntax",
+            DiagnosticKind::Semantic => "semantic",
+            DiagnosticKind::Codegen => "codegen",
+            DiagnosticKind::Internal => "internal",
+            DiagnosticKind::Driver => "driver",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,   // the compilation fails
+    Warning, // the compilation goes on
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// additional information pointing at a related location
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Span,
+}
+
+// suggested edit which replaces the span with the text
+// (an insertion if the span is empty)
+#[derive(Debug, Clone)]
+pub struct FixIt {
+    pub span: Span,
+    pub replacement: String,
+}
+
+// an error or a warning in the user's program (not a bug of ccr)
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub code: &'static str, // stable name for the tools (ex: "redefinition")
+    pub message: String,
+    pub span: Option<Span>, // None if the location is unknown
+    pub notes: Vec<Note>,
+    pub fixits: Vec<FixIt>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        kind: DiagnosticKind,
+        code: &'static str,
+        message: &str,
+        span: Option<Span>,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            kind,
+            code,
+            message: String::from(message),
+            span,
+            notes: Vec::new(),
+            fixits: Vec::new(),
+        }
+    }
+
+    pub fn warning(
+        kind: DiagnosticKind,
+        code: &'static str,
+        message: &str,
+        span: Span,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(kind, code, message, Some(span))
+        }
+    }
+
+    pub fn with_fixit(mut self, span: Span, replacement: &str) -> Diagnostic {
+        self.fixits.push(FixIt {
+            span,
+            replacement: String::from(replacement),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, message: &str, span: Span) -> Diagnostic {
+        self.notes.push(Note {
+            message: String::from(message),
+            span,
+        });
+        self
+    }
+
+    // render the diagnostic like clang
+    //   file:line:col: error: message [kind]
+    //       line | source code
+    //            |      ^~~~
+    //            |      fix-it
+    // file_id is the file being compiled, which is shown if the location is unknown
+    // (None for the errors of the driver, shown as "ccr: error: message")
+    pub fn render(&self, source_map: &SourceMap, file_id: Option<FileId>, color: bool) -> String {
+        let mut out = String::new();
+        let severity = self.severity.to_string();
+        let message = format!("{} [{}]", self.message, self.kind);
+        match self.span {
+            Some(span) => {
+                render_message(&mut out, source_map, span, &severity, &message, color);
+                // the fix-its on the same line are shown under the caret
+                let file = source_map.file(span.file);
+                for fixit in self.fixits.iter().filter(|f| f.span.line == span.line) {
+                    let indent = indent_of(file.line(span.line), fixit.span.col);
+                    let text = paint(&fixit.replacement, GREEN, color);
+                    let _ = writeln!(out, "{:>5} | {}{}", "", indent, text);
+                }
+            }
+            None => {
+                let name = match file_id {
+                    Some(file_id) => &source_map.file(file_id).name,
+                    None => "ccr",
+                };
+                render_header(&mut out, name, &severity, &message, color);
+            }
+        }
+        for note in &self.notes {
+            render_message(
+                &mut out,
+                source_map,
+                note.span,
+                "note",
+                &note.message,
+                color,
+            );
+        }
+        out
+    }
+}
+
+// wrap s with the escape sequence if the output is colored
+fn paint(s: &str, code: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", code, s, RESET)
+    } else {
+        String::from(s)
+    }
+}
+
+// location: severity: message
+fn render_header(out: &mut String, loc: &str, severity: &str, msg: &str, color: bool) {
+    let severity_color = match severity {
+        "error" => RED,
+        "warning" => MAGENTA,
+        _ => CYAN,
+    };
+    let _ = writeln!(
+        out,
+        "{} {} {}",
+        paint(&format!("{}:", loc), BOLD, color),
+        paint(&format!("{}:", severity), severity_color, color),
+        paint(msg, BOLD, color)
+    );
+}
+
+// the header, the source line and the caret/underline under the span
+fn render_message(
+    out: &mut String,
+    source_map: &SourceMap,
+    span: Span,
+    severity: &str,
+    msg: &str,
+    color: bool,
+) {
+    let file = source_map.file(span.file);
+    let loc = format!("{}:{}:{}", file.name, span.line, span.col);
+    render_header(out, &loc, severity, msg, color);
+
+    let line = file.line(span.line);
+    let line_start = span.start - (span.col - 1);
+    let col = (span.col - 1).min(line.len());
+    let indent = indent_of(line, span.col);
+    // the underline stops at the end of the line
+    let end = span
+        .end
+        .min(line_start + line.len())
+        .saturating_sub(line_start);
+    let width = line[col..end.max(col)].chars().count();
+    let mark = format!("^{}", "~".repeat(width.saturating_sub(1)));
+
+    let _ = writeln!(out, "{:>5} | {}", span.line, line);
+    let _ = writeln!(out, "{:>5} | {}{}", "", indent, paint(&mark, GREEN, color));
+}
+
+// whitespaces up to the column (1-origin) of the line
+// tabs are copied so that the marks line up with the source line
+// (a BOM is invisible and takes no space)
+fn indent_of(line: &str, col: usize) -> String {
+    let col = (col - 1).min(line.len());
+    line[..col]
+        .chars()
+        .filter(|&c| c != '\u{feff}')
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+// ----- machine-readable output -----
+
+// escape s as a JSON string literal
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// {"file": ..., "line": ..., "column": ..., "end_line": ..., "end_column": ..., "offset": ..., "length": ...}
+fn json_span(source_map: &SourceMap, span: Span) -> String {
+    let file = source_map.file(span.file);
+    let (end_line, end_col) = file.line_col(span.end);
+    format!(
+        "{{\"file\": {}, \"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}, \"offset\": {}, \"length\": {}}}",
+        json_str(&file.name),
+        span.line,
+        span.col,
+        end_line,
+        end_col,
+        span.start,
+        span.end - span.start
+    )
+}
+
+// all diagnostics as a JSON array
+// file_id of each diagnostic is the file being compiled when it was reported
+pub fn to_json(diags: &[(Option<FileId>, Diagnostic)], source_map: &SourceMap) -> String {
+    let mut records = Vec::new();
+    for (file_id, diag) in diags {
+        let primary = match diag.span {
+            Some(span) => json_span(source_map, span),
+            None => String::from("null"),
+        };
+        let secondary: Vec<String> = diag
+            .notes
+            .iter()
+            .map(|note| {
+                format!(
+                    "{{\"message\": {}, \"span\": {}}}",
+                    json_str(&note.message),
+                    json_span(source_map, note.span)
+                )
+            })
+            .collect();
+        let fixits: Vec<String> = diag
+            .fixits
+            .iter()
+            .map(|fixit| {
+                format!(
+                    "{{\"span\": {}, \"replacement\": {}}}",
+                    json_span(source_map, fixit.span),
+                    json_str(&fixit.replacement)
+                )
+            })
+            .collect();
+        let file = match file_id {
+            Some(file_id) => json_str(&source_map.file(*file_id).name),
+            None => String::from("null"),
+        };
+        records.push(format!(
+            "  {{\"severity\": {}, \"code\": {}, \"category\": {}, \"message\": {}, \"file\": {}, \"primary\": {}, \"secondary\": [{}], \"fixits\": [{}]}}",
+            json_str(&diag.severity.to_string()),
+            json_str(diag.code),
+            json_str(&diag.kind.to_string()),
+            json_str(&diag.message),
+            file,
+            primary,
+            secondary.join(", "),
+            fixits.join(", ")
+        ));
+    }
+    if records.is_empty() {
+        return String::from("[]\n");
+    }
+    format!("[\n{}\n]\n", records.join(",\n"))
+}
+
+// physicalLocation object of SARIF
+fn sarif_location(source_map: &SourceMap, span: Span) -> String {
+    let file = source_map.file(span.file);
+    format!(
+        "{{\"artifactLocation\": {{\"uri\": {}}}, \"region\": {}}}",
+        json_str(&file.name),
+        sarif_region(source_map, span)
+    )
+}
+
+// SARIF counts the columns and the offsets in characters (columnKind is unicodeCodePoints)
+// while the spans are in bytes
+fn sarif_region(source_map: &SourceMap, span: Span) -> String {
+    let file = source_map.file(span.file);
+    let (end_line, end_col) = file.line_col(span.end);
+    let chars = |start: usize, end: usize| file.src[start..end].chars().count();
+    let start_col = chars(span.start - (span.col - 1), span.start) + 1;
+    let end_col = chars(span.end - (end_col - 1), span.end) + 1;
+    format!(
+        "{{\"startLine\": {}, \"startColumn\": {}, \"endLine\": {}, \"endColumn\": {}, \"charOffset\": {}, \"charLength\": {}}}",
+        span.line,
+        start_col,
+        end_line,
+        end_col,
+        chars(0, span.start),
+        chars(span.start, span.end)
+    )
+}
+
+// what the fix-it does in words (the description of a SARIF fix)
+fn describe_fixit(source_map: &SourceMap, fixit: &FixIt) -> String {
+    let old = &source_map.file(fixit.span.file).src[fixit.span.start..fixit.span.end];
+    if old.is_empty() {
+        format!("insert '{}'", fixit.replacement)
+    } else if fixit.replacement.is_empty() {
+        format!("remove '{}'", old)
+    } else {
+        format!("replace '{}' with '{}'", old, fixit.replacement)
+    }
+}
+
+// all diagnostics as a SARIF 2.1.0 log
+pub fn to_sarif(diags: &[(Option<FileId>, Diagnostic)], source_map: &SourceMap) -> String {
+    let mut results = Vec::new();
+    for (file_id, diag) in diags {
+        // the errors of the driver are not in any file
+        let locations = match (diag.span, file_id) {
+            (Some(span), _) => format!(
+                "{{\"physicalLocation\": {}}}",
+                sarif_location(source_map, span)
+            ),
+            (None, Some(file_id)) => format!(
+                "{{\"physicalLocation\": {{\"artifactLocation\": {{\"uri\": {}}}}}}}",
+                json_str(&source_map.file(*file_id).name)
+            ),
+            (None, None) => String::new(),
+        };
+        let related: Vec<String> = diag
+            .notes
+            .iter()
+            .enumerate()
+            .map(|(i, note)| {
+                format!(
+                    "{{\"id\": {}, \"message\": {{\"text\": {}}}, \"physicalLocation\": {}}}",
+                    i,
+                    json_str(&note.message),
+                    sarif_location(source_map, note.span)
+                )
+            })
+            .collect();
+        let fixes: Vec<String> = diag
+            .fixits
+            .iter()
+            .map(|fixit| {
+                format!(
+                    "{{\"description\": {{\"text\": {}}}, \"artifactChanges\": [{{\"artifactLocation\": {{\"uri\": {}}}, \"replacements\": [{{\"deletedRegion\": {}, \"insertedContent\": {{\"text\": {}}}}}]}}]}}",
+                    json_str(&describe_fixit(source_map, fixit)),
+                    json_str(&source_map.file(fixit.span.file).name),
+                    sarif_region(source_map, fixit.span),
+                    json_str(&fixit.replacement)
+                )
+            })
+            .collect();
+        results.push(format!(
+            "        {{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}}, \"locations\": [{}], \"relatedLocations\": [{}], \"fixes\": [{}]}}",
+            json_str(diag.code),
+            json_str(&diag.severity.to_string()),
+            json_str(&diag.message),
+            locations,
+            related.join(", "),
+            fixes.join(", ")
+        ));
+    }
+    let mut out = String::new();
+    out.push_str("{\n");
+    out.push_str("  \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\",\n");
+    out.push_str("  \"version\": \"2.1.0\",\n");
+    out.push_str("  \"runs\": [\n    {\n");
+    let _ = writeln!(
+        out,
+        "      \"tool\": {{\"driver\": {{\"name\": \"ccr\", \"version\": {}, \"informationUri\": \"https://github.com/tamaroning/ccr\"}}}},",
+        json_str(env!("CARGO_PKG_VERSION"))
+    );
+    out.push_str("      \"columnKind\": \"unicodeCodePoints\",\n");
+    if results.is_empty() {
+        out.push_str("      \"results\": []\n");
+    } else {
+        let _ = writeln!(
+            out,
+            "      \"results\": [\n{}\n      ]",
+            results.join(",\n")
+        );
+    }
+    out.push_str("    }\n  ]\n}\n");
+    out
+}
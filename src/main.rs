@@ -1,80 +1,519 @@
+// Diagnostic is returned only on the error path, so its size doesn't matter
+#![allow(clippy::result_large_err)]
+
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use diagnostic::{Diagnostic, DiagnosticKind, Severity};
+use source::{FileId, SourceMap};
 
 mod codegen;
+mod diagnostic;
 mod parse;
+mod sema;
+mod source;
 mod tokenize;
 
+// the last stage the driver runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Assemble, // -S: stop after generating the assembly
+    Compile,  // -c: stop after generating the object file
+    Link,     // (default) link into an executable
+}
+
+// kind of an input file, decided by its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputKind {
+    C,   // compiled by ccr
+    Asm, // .s: assembled
+    Obj, // .o, .a: passed to the linker as is
+}
+
+// --color=auto|always|never
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorChoice {
+    Auto, // colored only if stderr is a terminal
+    Always,
+    Never,
+}
+
+// --diagnostics-format=text|json|sarif
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiagnosticsFormat {
+    Text,  // human-readable, printed as soon as reported
+    Json,  // printed at once at the end
+    Sarif, // printed at once at the end
+}
+
+// error that stops the driver
+#[derive(Debug)]
+enum Error {
+    Driver(Diagnostic), // errors of the driver itself (bad options, failed commands...)
+    Compile,            // errors in the source, already reported as diagnostics
+}
+
+impl From<Diagnostic> for Error {
+    fn from(diag: Diagnostic) -> Error {
+        Error::Driver(diag)
+    }
+}
+
+// errors and warnings of the driver have no location
+fn driver_error(code: &'static str, msg: &str) -> Diagnostic {
+    Diagnostic::new(DiagnosticKind::Driver, code, msg, None)
+}
+
+fn driver_warning(code: &'static str, msg: &str) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        ..driver_error(code, msg)
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    is_verbose: bool,
+    is_debug: bool,
+    stage: Stage,
+    out_path: Option<PathBuf>, // -o <path>
+    src_paths: Vec<PathBuf>,
+    link_args: Vec<String>, // -l, -L and -Wl, passed to the linker
+    color: bool,            // whether the diagnostics are colored
+    diagnostics_format: DiagnosticsFormat,
+    warnings: Vec<Diagnostic>, // about the options
+    errors: Vec<Diagnostic>,   // bad options (reported all at once like gcc)
+}
+
+// holds the sources and the diagnostics of all the input files
+struct Reporter {
+    source_map: SourceMap,
+    diags: Vec<(Option<FileId>, Diagnostic)>, // with the file being compiled (None for the driver)
+    format: DiagnosticsFormat,
+    color: bool,
+}
+
+impl Reporter {
+    fn new(opts: &Options) -> Reporter {
+        Reporter {
+            source_map: SourceMap::new(),
+            diags: Vec::new(),
+            format: opts.diagnostics_format,
+            color: opts.color,
+        }
+    }
+
+    fn report(&mut self, file_id: FileId, diag: Diagnostic) {
+        self.push(Some(file_id), diag);
+    }
+
+    // errors and warnings of the driver itself (bad options, failed commands...)
+    fn report_driver(&mut self, diag: Diagnostic) {
+        self.push(None, diag);
+    }
+
+    fn push(&mut self, file_id: Option<FileId>, diag: Diagnostic) {
+        if self.format == DiagnosticsFormat::Text {
+            eprint!("{}", diag.render(&self.source_map, file_id, self.color));
+        }
+        self.diags.push((file_id, diag));
+    }
+
+    // report the error and stop the compilation
+    fn error(&mut self, file_id: FileId, diag: Diagnostic) -> Error {
+        self.report(file_id, diag);
+        Error::Compile
+    }
+
+    // print the machine-readable diagnostics
+    fn finish(&self) {
+        match self.format {
+            DiagnosticsFormat::Text => (),
+            DiagnosticsFormat::Json => {
+                eprint!("{}", diagnostic::to_json(&self.diags, &self.source_map))
+            }
+            DiagnosticsFormat::Sarif => {
+                eprint!("{}", diagnostic::to_sarif(&self.diags, &self.source_map))
+            }
+        }
+    }
+}
+
 fn main() {
-    let mut is_quiet = false;
-    let mut is_debug = false;
+    // the errors of the options are reported in the requested format too
+    let opts = parse_args(env::args().skip(1));
+    let mut reporter = Reporter::new(&opts);
+    for diag in &opts.warnings {
+        reporter.report_driver(diag.clone());
+    }
+    for diag in &opts.errors {
+        reporter.report_driver(diag.clone());
+    }
+    if !opts.errors.is_empty() {
+        reporter.finish();
+        process::exit(1);
+    }
 
-    let argv: Vec<String> = env::args().collect();
+    // intermediate files are removed even if a later stage fails
+    let mut tmp_files = Vec::new();
+    let result = run(&opts, &mut reporter, &mut tmp_files);
+    for path in &tmp_files {
+        let _ = fs::remove_file(path);
+    }
+    if let Err(Error::Driver(diag)) = &result {
+        reporter.report_driver(diag.clone());
+    }
+    reporter.finish();
+    if result.is_err() {
+        process::exit(1);
+    }
+}
 
-    // prepare the source file
-    let mut src_path = Path::new("");
-    let src_display = src_path.display();
+// options of gcc which don't change the output of ccr (ex: -O2, -g, -Wall, -std=c11)
+fn is_ignored_option(arg: &str) -> bool {
+    ["-O", "-g", "-W", "-f", "-std=", "-pedantic"]
+        .iter()
+        .any(|prefix| arg.starts_with(prefix))
+        || arg == "-w"
+        || arg == "-pipe"
+}
 
-    // process the arguments
-    for arg in &argv {
-        if arg == "-q" {
-            is_quiet = true;
+fn parse_args(args: impl Iterator<Item = String>) -> Options {
+    let mut is_verbose = false;
+    let mut is_debug = false;
+    let mut stage = Stage::Link;
+    let mut out_path = None;
+    let mut src_paths = Vec::new();
+    let mut link_args = Vec::new();
+    let mut color = ColorChoice::Auto;
+    let mut diagnostics_format = DiagnosticsFormat::Text;
+    let mut warnings = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "-v" {
+            is_verbose = true;
+        } else if arg == "-q" {
+            // the progress is printed only with -v
         } else if arg == "-d" {
             is_debug = true;
+        } else if arg == "-S" {
+            stage = Stage::Assemble;
+        } else if arg == "-c" {
+            // -S wins if both are given, like gcc
+            if stage != Stage::Assemble {
+                stage = Stage::Compile;
+            }
+        } else if arg == "-o" {
+            match args.next() {
+                Some(path) => out_path = Some(PathBuf::from(path)),
+                None => errors.push(driver_error(
+                    "missing-argument",
+                    "missing filename after '-o'",
+                )),
+            }
+        } else if let Some(path) = arg.strip_prefix("-o") {
+            out_path = Some(PathBuf::from(path));
+        } else if let Some(choice) = arg.strip_prefix("--color=") {
+            match choice {
+                "auto" => color = ColorChoice::Auto,
+                "always" => color = ColorChoice::Always,
+                "never" => color = ColorChoice::Never,
+                _ => {
+                    let msg = format!("invalid argument '{}' to '--color='", choice);
+                    errors.push(driver_error("invalid-option-argument", &msg));
+                }
+            }
+        } else if let Some(format) = arg.strip_prefix("--diagnostics-format=") {
+            match format {
+                "text" => diagnostics_format = DiagnosticsFormat::Text,
+                "json" => diagnostics_format = DiagnosticsFormat::Json,
+                "sarif" => diagnostics_format = DiagnosticsFormat::Sarif,
+                _ => {
+                    let msg = format!("invalid argument '{}' to '--diagnostics-format='", format);
+                    errors.push(driver_error("invalid-option-argument", &msg));
+                }
+            }
+        } else if let Some(flag) = ["-I", "-D", "-U"].iter().find(|f| arg.starts_with(*f)) {
+            // ccr has no preprocessor, so there is nothing to include or to define
+            let value = match &arg[2..] {
+                "" => match args.next() {
+                    Some(value) => value,
+                    None => {
+                        let msg = format!("missing argument to '{}'", flag);
+                        errors.push(driver_error("missing-argument", &msg));
+                        continue;
+                    }
+                },
+                value => value.to_string(),
+            };
+            if *flag != "-I" {
+                let msg = format!(
+                    "'{}{}' is ignored since ccr has no preprocessor",
+                    flag, value
+                );
+                warnings.push(driver_warning("ignored-option", &msg));
+            }
+        } else if arg.starts_with("-l") || arg.starts_with("-L") || arg.starts_with("-Wl,") {
+            link_args.push(arg);
+        } else if is_ignored_option(&arg) {
+            // accepted so that ccr can be used as CC in the Makefiles
+        } else if arg.starts_with('-') {
+            let msg = format!("unrecognized command-line option '{}'", arg);
+            errors.push(driver_error("unknown-option", &msg));
         } else {
-            src_path = Path::new(arg);
+            src_paths.push(PathBuf::from(arg));
         }
     }
 
-    if src_path.to_str() == Some("") {
-        panic!("No input file");
+    if src_paths.is_empty() {
+        errors.push(driver_error("no-input-files", "no input files"));
+    }
+    if out_path.is_some() && stage != Stage::Link && src_paths.len() > 1 {
+        errors.push(driver_error(
+            "conflicting-options",
+            "cannot specify '-o' with '-c' or '-S' with multiple files",
+        ));
+    }
+    Options {
+        is_verbose,
+        is_debug,
+        stage,
+        out_path,
+        src_paths,
+        link_args,
+        color: match color {
+            ColorChoice::Auto => io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        },
+        diagnostics_format,
+        warnings,
+        errors,
+    }
+}
+
+fn input_kind(path: &Path) -> InputKind {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("s") => InputKind::Asm,
+        Some("o") | Some("a") => InputKind::Obj,
+        _ => InputKind::C,
+    }
+}
+
+// compile each input file and run the assembler and the linker as needed
+// every input is compiled even if some of them fail, so that all the errors are reported
+fn run(opts: &Options, reporter: &mut Reporter, tmp_files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let mut obj_paths = Vec::new();
+    let mut has_failed = false;
+    for (i, src_path) in opts.src_paths.iter().enumerate() {
+        match build(opts, reporter, i, src_path, tmp_files) {
+            Ok(Some(obj_path)) => obj_paths.push(obj_path),
+            Ok(None) => (),
+            Err(Error::Driver(diag)) => {
+                reporter.report_driver(diag);
+                has_failed = true;
+            }
+            Err(Error::Compile) => has_failed = true,
+        }
+    }
+    // the errors are already reported
+    if has_failed {
+        return Err(Error::Compile);
+    }
+    if opts.stage != Stage::Link {
+        return Ok(());
     }
 
+    let exe_path = opts
+        .out_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("a.out"));
+    link(&obj_paths, &opts.link_args, &exe_path)?;
+    Ok(())
+}
+
+// compile and assemble the i-th input up to the stage of the options
+// returns the object file to link (None if the stage stops before the assembler)
+fn build(
+    opts: &Options,
+    reporter: &mut Reporter,
+    i: usize,
+    src_path: &Path,
+    tmp_files: &mut Vec<PathBuf>,
+) -> Result<Option<PathBuf>, Error> {
+    // the default output is placed in the working directory like gcc
+    let default_out = |ext: &str| -> PathBuf {
+        let stem = src_path.file_stem().unwrap_or_default();
+        PathBuf::from(stem).with_extension(ext)
+    };
+    let kind = input_kind(src_path);
+
+    let asm_path = match (kind, opts.stage) {
+        (InputKind::C, Stage::Assemble) => {
+            opts.out_path.clone().unwrap_or_else(|| default_out("s"))
+        }
+        (InputKind::C, _) => tmp_path(i, src_path, "s", tmp_files),
+        _ => src_path.to_path_buf(),
+    };
+    if kind == InputKind::C {
+        compile(opts, reporter, src_path, &asm_path)?;
+    }
+    if opts.stage == Stage::Assemble {
+        return Ok(None);
+    }
+
+    let obj_path = match (kind, opts.stage) {
+        (InputKind::Obj, _) => src_path.to_path_buf(),
+        (_, Stage::Compile) => opts.out_path.clone().unwrap_or_else(|| default_out("o")),
+        _ => tmp_path(i, src_path, "o", tmp_files),
+    };
+    if kind != InputKind::Obj {
+        assemble(&asm_path, &obj_path)?;
+    }
+    Ok(Some(obj_path))
+}
+
+// return a path for an intermediate file and register it for the removal
+// the index of the input keeps the names unique even if the stems are the same
+fn tmp_path(i: usize, src_path: &Path, ext: &str, tmp_files: &mut Vec<PathBuf>) -> PathBuf {
+    let stem = src_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("ccr-{}-{}-{}.{}", process::id(), i, stem, ext);
+    let path = env::temp_dir().join(name);
+    tmp_files.push(path.clone());
+    path
+}
+
+// tokenize, parse and generate the assembly of the source file
+fn compile(
+    opts: &Options,
+    reporter: &mut Reporter,
+    src_path: &Path,
+    asm_path: &Path,
+) -> Result<(), Error> {
+    // the progress goes to stderr like cc -v
+    let is_verbose = opts.is_verbose && !opts.is_debug;
+    let src_display = src_path.display();
+
     // open path as read-only
-    let mut src_file = match File::open(&src_path) {
-        Err(why) => panic!("couldn't open {}: {}", src_display, why.to_string()),
+    let mut src_file = match File::open(src_path) {
+        Err(why) => {
+            let code = match why.kind() {
+                io::ErrorKind::NotFound => "file-not-found",
+                _ => "open-failed",
+            };
+            let msg = format!("couldn't open {}: {}", src_display, why);
+            return Err(driver_error(code, &msg).into());
+        }
         Ok(file) => file,
     };
 
     // load the content of the soure file
     let mut src_string = String::new();
-    match src_file.read_to_string(&mut src_string) {
-        Err(why) => panic!("couldn't read {}: {}", src_display, why.to_string()),
-        Ok(_) => (), //print!("{} contains:\n{}", src_display, src_string),
+    if let Err(why) = src_file.read_to_string(&mut src_string) {
+        let msg = format!("couldn't read {}: {}", src_display, why);
+        return Err(driver_error("read-failed", &msg).into());
     }
 
     // tokenize the source code
-    if !is_quiet && !is_debug {
-        println!("Tokenizing input...");
+    if is_verbose {
+        eprintln!("Compiling {}", src_display);
+        eprintln!("Tokenizing input...");
     }
-    let tokens = tokenize::tokenize(src_string);
-    if !is_quiet && !is_debug {
-        println!("Done");
+    let file_id = reporter
+        .source_map
+        .add_file(&src_display.to_string(), src_string);
+    let mut warnings = Vec::new();
+    let result = tokenize::tokenize(reporter.source_map.file(file_id), &mut warnings);
+    for warning in warnings.drain(..) {
+        reporter.report(file_id, warning);
     }
-    if is_debug {
+    let tokens = result.map_err(|diag| reporter.error(file_id, diag))?;
+    if is_verbose {
+        eprintln!("Done");
+    }
+    if opts.is_debug {
         println!("{:?}", tokens);
     }
 
     // generate AST with Token list
-    if !is_quiet && !is_debug {
-        println!("Parsing tokens...");
+    if is_verbose {
+        eprintln!("Parsing tokens...");
+    }
+    let result = parse::parse(tokens, &mut warnings);
+    for warning in warnings.drain(..) {
+        reporter.report(file_id, warning);
+    }
+    let asts = result.map_err(|diag| reporter.error(file_id, diag))?;
+    if is_verbose {
+        eprintln!("Done");
+    }
+
+    // give the types to the expressions and check them
+    if is_verbose {
+        eprintln!("Analyzing AST...");
+    }
+    let result = sema::analyze(asts, &mut warnings);
+    for warning in warnings.drain(..) {
+        reporter.report(file_id, warning);
     }
-    let asts = parse::parse(tokens);
-    if !is_quiet && !is_debug {
-        println!("Done");
+    let asts = result.map_err(|diag| reporter.error(file_id, diag))?;
+    if is_verbose {
+        eprintln!("Done");
     }
-    if is_debug {
+    if opts.is_debug {
         println!("{:?}", asts);
     }
 
-    // generate the assembly with AST list, then write it to tmp.s
-    if !is_quiet && !is_debug {
-        println!("Generating assembly...");
+    // generate the assembly with AST list, then write it to asm_path
+    if is_verbose {
+        eprintln!("Generating assembly...");
     }
-    codegen::codegen(asts, "tmp.s");
-    if !is_quiet && !is_debug {
-        println!("Done");
+    let asm_file = match File::create(asm_path) {
+        Err(why) => {
+            let msg = format!("couldn't create {}: {}", asm_path.display(), why);
+            return Err(driver_error("create-failed", &msg).into());
+        }
+        Ok(file) => file,
+    };
+    codegen::codegen(asts, asm_file).map_err(|diag| reporter.error(file_id, diag))?;
+    if is_verbose {
+        eprintln!("Done");
+    }
+    Ok(())
+}
+
+// assemble the .s file into an .o file with the system assembler
+fn assemble(asm_path: &Path, obj_path: &Path) -> Result<(), Diagnostic> {
+    let mut cmd = Command::new("as");
+    cmd.arg("-o").arg(obj_path).arg(asm_path);
+    run_command(cmd)
+}
+
+// link the object files into an executable with the system compiler driver,
+// which knows where the C runtime and libc live
+// (the libraries follow the objects which use them)
+fn link(obj_paths: &[PathBuf], link_args: &[String], exe_path: &Path) -> Result<(), Diagnostic> {
+    let mut cmd = Command::new("cc");
+    cmd.arg("-o").arg(exe_path).args(obj_paths).args(link_args);
+    run_command(cmd)
+}
+
+fn run_command(mut cmd: Command) -> Result<(), Diagnostic> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    match cmd.status() {
+        Err(why) => {
+            let msg = format!("couldn't run {}: {}", program, why);
+            Err(driver_error("command-not-found", &msg))
+        }
+        Ok(status) if !status.success() => {
+            let msg = format!("{} failed with {}", program, status);
+            Err(driver_error("command-failed", &msg))
+        }
+        Ok(_) => Ok(()),
     }
 }
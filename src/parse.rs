@@ -1,72 +1,441 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::sema;
+use crate::source::Span;
 use crate::tokenize::Token;
 use crate::tokenize::TokenKind;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
 
 #[test]
 fn test_parse() {
+    use crate::source::SourceMap;
     use crate::tokenize::tokenize;
-    let tokens = tokenize(String::from("int a, b; int c = a;"));
+    let mut map = SourceMap::new();
+    let id = map.add_file("test.c", String::from("int a, b; int c = 3;"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
     println!("{:?}", tokens);
-    let ast = parse(tokens);
+    let ast = parse(tokens, &mut Vec::new()).unwrap();
     println!("{:?}", ast);
+
+    // file-scope initializers must be constant
+    let id = map.add_file("test.c", String::from("int a, b; int c = a;"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    assert!(parse(tokens, &mut Vec::new()).is_err());
+
+    // type specifiers can be combined in any order
+    let id = map.add_file("test.c", String::from("long unsigned int a; short int b;"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    assert!(parse(tokens, &mut Vec::new()).is_ok());
+    let id = map.add_file("test.c", String::from("long char c;"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    assert!(parse(tokens, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_struct_layout() {
+    use crate::source::SourceMap;
+    use crate::tokenize::tokenize;
+    let mut map = SourceMap::new();
+    let src = "struct s { char c; int a; union { short x; long y; }; char d; } g;";
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let asts = parse(tokens, &mut Vec::new()).unwrap();
+    let s = match asts[0].kind() {
+        NodeKind::GVarDecl {
+            ty: Type::Struct(s),
+            ..
+        } => s,
+        _ => panic!("expected a struct variable"),
+    };
+    // the members are aligned, and the members of the anonymous union share the offset
+    let offsets: Vec<usize> = ["c", "a", "x", "y", "d"]
+        .iter()
+        .map(|name| s.member(name).unwrap().offset)
+        .collect();
+    assert_eq!(offsets, [0, 4, 8, 8, 16]);
+    let ty = Type::Struct(s);
+    assert_eq!((ty.size(), ty.align()), (24, 8));
+    assert_eq!(ty.to_string(), "struct s");
+}
+
+#[test]
+fn test_type_display() {
+    let ptr = |ty| Type::Ptr(Box::new(ty));
+    assert_eq!(ptr(Type::Char).to_string(), "char *");
+    assert_eq!(ptr(ptr(Type::Int)).to_string(), "int **");
+    let array = |ty, len| Type::Array(Box::new(ty), len);
+    assert_eq!(array(array(Type::Int, 4), 3).to_string(), "int[3][4]");
+    assert_eq!(array(array(Type::Int, 4), 3).size(), 48);
+    assert_eq!(ptr(array(Type::Int, 4)).to_string(), "int (*)[4]");
+    let func = |params, is_variadic| Type::Func {
+        ret: Box::new(Type::Int),
+        params,
+        is_variadic,
+    };
+    assert_eq!(func(Some(vec![]), false).to_string(), "int (void)");
+    assert_eq!(func(None, false).to_string(), "int ()");
+    let printf = func(Some(vec![ptr(Type::Char)]), true);
+    assert_eq!(printf.to_string(), "int (char *, ...)");
+    assert_eq!(
+        ptr(func(Some(vec![Type::Int]), false)).to_string(),
+        "int (*)(int)"
+    );
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Void,
+    Char, // char and signed char
+    Short,
     Int,
+    Long, // long and long long
+    UChar,
+    UShort,
+    UInt,
+    ULong, // unsigned long and unsigned long long
     Ptr(Box<Type>),
+    Array(Box<Type>, usize), // element type, length
+    // params is None if the function is declared without a prototype like f()
+    Func {
+        ret: Box<Type>,
+        params: Option<Vec<Type>>,
+        is_variadic: bool,
+    },
+    Struct(StructRef), // struct and union
+}
+
+// struct or union shared by all the declarations which refer to it
+// two structs are the same type only if they come from the same declaration
+#[derive(Clone)]
+pub struct StructRef(Rc<RefCell<StructDef>>);
+
+#[derive(Debug)]
+struct StructDef {
+    tag: Option<String>,
+    is_union: bool,
+    span: Span, // location of the tag (or the keyword) in the declaration
+    members: Vec<Member>,
+    size: usize,
+    align: usize,
+    is_complete: bool, // false until the members are defined
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: usize, // offset from the beginning of the struct
+}
+
+impl StructRef {
+    fn new(tag: Option<String>, is_union: bool, span: Span) -> StructRef {
+        StructRef(Rc::new(RefCell::new(StructDef {
+            tag,
+            is_union,
+            span,
+            members: Vec::new(),
+            size: 0,
+            align: 1,
+            is_complete: false,
+        })))
+    }
+
+    fn is_union(&self) -> bool {
+        self.0.borrow().is_union
+    }
+
+    fn is_complete(&self) -> bool {
+        self.0.borrow().is_complete
+    }
+
+    fn span(&self) -> Span {
+        self.0.borrow().span
+    }
+
+    fn member(&self, name: &str) -> Option<Member> {
+        let def = self.0.borrow();
+        def.members.iter().find(|m| m.name == name).cloned()
+    }
+
+    fn members(&self) -> Vec<Member> {
+        self.0.borrow().members.clone()
+    }
+}
+
+impl PartialEq for StructRef {
+    fn eq(&self, other: &StructRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// the members are not printed since they can refer to the struct itself
+impl fmt::Debug for StructRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Type::Struct(self.clone()))
+    }
+}
+
+impl Type {
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Void | Type::Char | Type::UChar | Type::Func { .. } => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt => 4,
+            Type::Long | Type::ULong | Type::Ptr(_) => 8,
+            Type::Array(elem, len) => elem.size() * len,
+            Type::Struct(s) => s.0.borrow().size,
+        }
+    }
+
+    // scalar types are aligned to their size, and arrays to their elements
+    pub fn align(&self) -> usize {
+        match self {
+            Type::Array(elem, _) => elem.align(),
+            Type::Struct(s) => s.0.borrow().align,
+            _ => self.size(),
+        }
+    }
+
+    // void and the structs declared without the members have no size
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            Type::Void => true,
+            Type::Struct(s) => !s.is_complete(),
+            _ => false,
+        }
+    }
+
+    // arrays are converted to the pointer to the first element in expressions,
+    // and functions to the pointer to the function
+    pub fn decay(self) -> Type {
+        match self {
+            Type::Array(elem, _) => Type::Ptr(elem),
+            ty @ Type::Func { .. } => Type::Ptr(Box::new(ty)),
+            ty => ty,
+        }
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Type::UChar | Type::UShort | Type::UInt | Type::ULong)
+    }
+
+    // integer promotion: the types narrower than int are converted to int
+    pub fn promoted(&self) -> Type {
+        match self {
+            Type::Char | Type::Short | Type::UChar | Type::UShort => Type::Int,
+            ty => ty.clone(),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Char
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::UChar
+                | Type::UShort
+                | Type::UInt
+                | Type::ULong
+        )
+    }
+
+    // whether two declarations of a function agree
+    // a declaration without a prototype agrees with any parameters
+    fn is_compatible_func(&self, other: &Type) -> bool {
+        match (self, other) {
+            (
+                Type::Func {
+                    ret: r1,
+                    params: p1,
+                    is_variadic: v1,
+                },
+                Type::Func {
+                    ret: r2,
+                    params: p2,
+                    is_variadic: v2,
+                },
+            ) => {
+                r1 == r2
+                    && match (p1, p2) {
+                        (Some(p1), Some(p2)) => p1 == p2 && v1 == v2,
+                        _ => true,
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+// dimensions of the array like [3][4] and its element type
+fn array_dims(ty: &Type) -> (String, &Type) {
+    let mut dims = String::new();
+    let mut ty = ty;
+    while let Type::Array(elem, len) = ty {
+        dims += &format!("[{}]", len);
+        ty = elem;
+    }
+    (dims, ty)
+}
+
+// spelled like C (ex: int, char *, int[3][4], int (int, char *))
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Char => write!(f, "char"),
+            Type::Short => write!(f, "short"),
+            Type::Int => write!(f, "int"),
+            Type::Long => write!(f, "long"),
+            Type::UChar => write!(f, "unsigned char"),
+            Type::UShort => write!(f, "unsigned short"),
+            Type::UInt => write!(f, "unsigned int"),
+            Type::ULong => write!(f, "unsigned long"),
+            Type::Ptr(base) => match **base {
+                Type::Ptr(_) => write!(f, "{}*", base),
+                Type::Array(..) => {
+                    let (dims, elem) = array_dims(base);
+                    write!(f, "{} (*){}", elem, dims)
+                }
+                Type::Func {
+                    ref ret,
+                    ref params,
+                    is_variadic,
+                } => write!(f, "{} (*)({})", ret, param_names(params, is_variadic)),
+                _ => write!(f, "{} *", base),
+            },
+            Type::Array(..) => {
+                let (dims, elem) = array_dims(self);
+                write!(f, "{}{}", elem, dims)
+            }
+            Type::Struct(s) => {
+                let def = s.0.borrow();
+                let keyword = if def.is_union { "union" } else { "struct" };
+                match &def.tag {
+                    Some(tag) => write!(f, "{} {}", keyword, tag),
+                    None => write!(f, "{} <anonymous>", keyword),
+                }
+            }
+            Type::Func {
+                ret,
+                params,
+                is_variadic,
+            } => write!(f, "{} ({})", ret, param_names(params, *is_variadic)),
+        }
+    }
+}
+
+// parameter list of the function type (ex: "int, char *", "void")
+fn param_names(params: &Option<Vec<Type>>, is_variadic: bool) -> String {
+    let mut names: Vec<String> = match params {
+        Some(params) if params.is_empty() && !is_variadic => vec![String::from("void")],
+        Some(params) => params.iter().map(|ty| ty.to_string()).collect(),
+        None => Vec::new(),
+    };
+    if is_variadic {
+        names.push(String::from("..."));
+    }
+    names.join(", ")
+}
+
+// initial value of a global variable
+#[derive(Debug, Clone)]
+pub enum GInit {
+    Int(i64),     // arithmetic constant
+    Str(Vec<u8>), // pointer to a string literal
+    Addr(String), // address of a global variable
 }
 
 #[derive(Debug, Clone)]
 pub enum NodeKind {
     FuncDecl {
         name: String,
-        args: Box<Vec<(usize, Type)>>,
+        args: Vec<(usize, Type)>,
         ret_type: Type,
         frame_size: usize,
-        stmts: Box<Vec<AST>>,
+        stmts: Vec<AST>,
+    },
+    // global variable definitions (placed in .bss if init is None)
+    GVarDecl {
+        name: String,
+        ty: Type,
+        init: Option<GInit>,
     },
 
     // --- Expression ---
-    Num(isize),                 // integers
+    Num(i64, Type),             // integers (unsigned long is kept as the same bits)
+    Str(Vec<u8>),               // string literals (without the terminating NUL)
     Assign(Box<AST>, Box<AST>), // = (assignment)
     Plus(Box<AST>, Box<AST>),
     Minus(Box<AST>, Box<AST>),
     Mul(Box<AST>, Box<AST>),
     Div(Box<AST>, Box<AST>), // +,-,*,/
+    Mod(Box<AST>, Box<AST>), // %
+    BitAnd(Box<AST>, Box<AST>),
+    BitOr(Box<AST>, Box<AST>),
+    BitXor(Box<AST>, Box<AST>),
+    BitNot(Box<AST>), // &, |, ^, ~
+    Shl(Box<AST>, Box<AST>),
+    Shr(Box<AST>, Box<AST>), // <<, >>
     Eq(Box<AST>, Box<AST>),
     Ne(Box<AST>, Box<AST>),
     Le(Box<AST>, Box<AST>),
     Lt(Box<AST>, Box<AST>), // ==,!=,<=,<
+    LogAnd(Box<AST>, Box<AST>),
+    LogOr(Box<AST>, Box<AST>),
+    Not(Box<AST>), // &&, ||, !
     Deref(Box<AST>),
-    Addr(Box<AST>), // *, &
+    Addr(Box<AST>),            // *, &
+    Cast(Box<AST>),            // conversion to the type of the node (inserted by sema)
+    Comma(Box<AST>, Box<AST>), // evaluates l, discards it and evaluates r (made by sema)
+    // A op= B, where the operator node holds A and B,
+    // and the offset of the temporary pointer to A if A has side effects
+    OpAssign(Box<AST>, Option<usize>),
+    PostInc(Box<AST>), // A++ (the operand is A += 1)
+    PostDec(Box<AST>), // A-- (the operand is A -= 1)
+    #[allow(dead_code)]
     Var {
         name: String,
         offset: usize,
         ty: Type,
     }, // local variables (offset from rbp)
+    GVar {
+        name: String,
+        ty: Type,
+    }, // global variables
+    Member {
+        base: Box<AST>,
+        name: String,
+        offset: usize,
+        ty: Type,
+    }, // member access (s.x, and p->x is (*p).x)
+    Index(Box<AST>, Box<AST>), // a[i] (sema makes it *(a + i))
     FuncCall {
         name: String,
-        argv: Box<Vec<AST>>,
+        argv: Vec<AST>,
+        ty: Type,   // type of the function
+        decl: Span, // location of the declaration
+    },
+    FuncPtrCall {
+        callee: Box<AST>, // function pointer
+        argv: Vec<AST>,
     }, // function call
 
     // --- Statement ---
     ExprStmt(Box<AST>),
-    Block(Box<Vec<AST>>), // {} block
-    Return(Box<AST>),     // return statement
+    Block(Vec<AST>),  // {} block
+    Return(Box<AST>), // return statement
     // if([cond(expr)])[then(stmt)] else [els(stmt)]
     If {
         cond: Box<AST>,
         then: Box<AST>,
         els: Box<AST>,
     },
-    //while([cond(expr)]) [proc(stmt)]
-    While {
-        cond: Box<AST>,
-        proc: Box<AST>,
-    },
     // for([A(expr)];[B(expr)];[C(expr)]) [D(stmt)]
     For {
         a: Box<AST>,
@@ -77,27 +446,202 @@ pub enum NodeKind {
 }
 
 // Abstract syntax tree
+// Nil is only a placeholder, so the nodes are not boxed to make it smaller
+#[allow(clippy::upper_case_acronyms, clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum AST {
     Nil,
     Node {
-        kind: NodeKind, // Node kind
-                        // left and right side value (used only when the node is calculation)
+        kind: NodeKind,   // Node kind
+        span: Span,       // location of the construct in the source
+        ty: Option<Type>, // type of the expression (None until the semantic analysis)
     },
 }
 
 impl AST {
     pub fn kind(&self) -> NodeKind {
         match self.clone() {
-            AST::Node { kind: k } => k,
+            AST::Node { kind: k, .. } => k,
             _ => panic!("Nil doesn't have kind"),
         }
     }
+
+    // type of the expression given by the semantic analysis
+    pub fn ty(&self) -> Type {
+        match self {
+            AST::Node { ty: Some(ty), .. } => ty.clone(),
+            _ => panic!("the expression is not analyzed"),
+        }
+    }
+
+    // Nil has no location and returns the default span
+    pub fn span(&self) -> Span {
+        match self {
+            AST::Node { span, .. } => *span,
+            AST::Nil => Span::default(),
+        }
+    }
 }
 
-fn new_node_num(val: isize) -> AST {
+// the type is given by the semantic analysis
+pub fn new_node(kind: NodeKind, span: Span) -> AST {
     AST::Node {
-        kind: NodeKind::Num(val),
+        kind,
+        span,
+        ty: None,
+    }
+}
+
+fn new_node_num(val: i64, span: Span) -> AST {
+    new_node(NodeKind::Num(val, Type::Int), span)
+}
+
+// round n up to the multiple of align
+fn align_to(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
+}
+
+// binary operator node which spans from lhs to rhs
+fn new_binary(kind: fn(Box<AST>, Box<AST>) -> NodeKind, lhs: AST, rhs: AST) -> AST {
+    let span = lhs.span().to(rhs.span());
+    new_node(kind(Box::new(lhs), Box::new(rhs)), span)
+}
+
+// whether evaluating the expression changes something (ex: i++, f())
+fn has_side_effects(ast: &AST) -> bool {
+    let kind = match ast {
+        AST::Node { kind, .. } => kind,
+        AST::Nil => return false,
+    };
+    match kind {
+        NodeKind::Assign(..)
+        | NodeKind::OpAssign(..)
+        | NodeKind::PostInc(_)
+        | NodeKind::PostDec(_)
+        | NodeKind::FuncCall { .. }
+        | NodeKind::FuncPtrCall { .. } => true,
+        NodeKind::Plus(l, r)
+        | NodeKind::Minus(l, r)
+        | NodeKind::Mul(l, r)
+        | NodeKind::Div(l, r)
+        | NodeKind::Mod(l, r)
+        | NodeKind::BitAnd(l, r)
+        | NodeKind::BitOr(l, r)
+        | NodeKind::BitXor(l, r)
+        | NodeKind::Shl(l, r)
+        | NodeKind::Shr(l, r)
+        | NodeKind::Eq(l, r)
+        | NodeKind::Ne(l, r)
+        | NodeKind::Le(l, r)
+        | NodeKind::Lt(l, r)
+        | NodeKind::LogAnd(l, r)
+        | NodeKind::LogOr(l, r)
+        | NodeKind::Comma(l, r)
+        | NodeKind::Index(l, r) => has_side_effects(l) || has_side_effects(r),
+        NodeKind::BitNot(operand)
+        | NodeKind::Not(operand)
+        | NodeKind::Deref(operand)
+        | NodeKind::Addr(operand)
+        | NodeKind::Cast(operand) => has_side_effects(operand),
+        NodeKind::Member { base, .. } => has_side_effects(base),
+        _ => false,
+    }
+}
+
+const COMPOUND_ASSIGN_OPS: [&str; 10] =
+    ["+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>="];
+
+const TYPE_SPECIFIERS: [&str; 7] = ["void", "char", "short", "int", "long", "signed", "unsigned"];
+
+// the number of each type specifier in a declaration
+#[derive(Debug, Default)]
+struct TypeSpecifiers {
+    void: usize,
+    char: usize,
+    short: usize,
+    int: usize,
+    long: usize,
+    signed: usize,
+    unsigned: usize,
+}
+
+impl TypeSpecifiers {
+    fn count(&mut self, name: &str) {
+        match name {
+            "void" => self.void += 1,
+            "char" => self.char += 1,
+            "short" => self.short += 1,
+            "int" => self.int += 1,
+            "long" => self.long += 1,
+            "signed" => self.signed += 1,
+            _ => self.unsigned += 1,
+        }
+    }
+
+    // type of the combination (ex: unsigned long int), or None if it is invalid
+    fn ty(&self) -> Option<Type> {
+        let is_unsigned = match (self.signed, self.unsigned) {
+            (0, 0) | (1, 0) => false,
+            (0, 1) => true,
+            _ => return None,
+        };
+        let has_sign = self.signed + self.unsigned > 0;
+        let (signed, unsigned) = match (self.void, self.char, self.short, self.int, self.long) {
+            (1, 0, 0, 0, 0) if !has_sign => return Some(Type::Void),
+            (0, 1, 0, 0, 0) => (Type::Char, Type::UChar),
+            (0, 0, 1, 0..=1, 0) => (Type::Short, Type::UShort),
+            (0, 0, 0, 0..=1, 0) => (Type::Int, Type::UInt),
+            (0, 0, 0, 0..=1, 1..=2) => (Type::Long, Type::ULong),
+            _ => return None,
+        };
+        Some(if is_unsigned { unsigned } else { signed })
+    }
+}
+
+// variable visible in a scope
+#[derive(Debug, Clone)]
+enum VarScope {
+    Local(usize, Type, Span), // offset from RBP, type, location of the declaration
+    Global(Type, Span, bool), // type, location of the declaration, whether it has an initializer
+    Func(Type, Span, bool),   // type, location of the declaration, whether it has a body
+    Enum(i64, Span),          // value and location of the enumerator
+    Typedef(Type, Span),      // type and location of the typedef name
+}
+
+impl VarScope {
+    // location of the declaration
+    fn span(&self) -> Span {
+        match self {
+            VarScope::Local(_, _, span)
+            | VarScope::Global(_, span, _)
+            | VarScope::Func(_, span, _)
+            | VarScope::Enum(_, span)
+            | VarScope::Typedef(_, span) => *span,
+        }
+    }
+}
+
+// struct, union or enum tag visible in a scope
+#[derive(Debug, Clone)]
+enum Tag {
+    Struct(StructRef),
+    Enum(Span, bool), // location of the tag, whether the enumerators are defined
+}
+
+impl Tag {
+    fn keyword(&self) -> &str {
+        match self {
+            Tag::Struct(s) if s.is_union() => "union",
+            Tag::Struct(_) => "struct",
+            Tag::Enum(..) => "enum",
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Tag::Struct(s) => s.span(),
+            Tag::Enum(span, _) => *span,
+        }
     }
 }
 
@@ -108,17 +652,30 @@ struct Parser {
 
     // 以下は関数定義毎にリセット
     offset: usize, // current stack frame size (increase by 8 when a new local var is defined)
-    locals: HashMap<String, (usize, Type)>, // local variables list <name, offset from RBP>
+
+    // stack of the scopes <name, variable>
+    // the bottom is the file scope, and each function and block pushes a new one
+    // the functions are declared in the file scope
+    scopes: Vec<HashMap<String, VarScope>>,
+    // struct, union and enum tags in the same scopes as scopes
+    tags: Vec<HashMap<String, Tag>>,
+
+    warnings: Vec<Diagnostic>,
 }
 
-pub fn parse(tokens: Vec<Token>) -> Vec<AST> {
+// the warnings are appended to warnings even if the parse fails
+pub fn parse(tokens: Vec<Token>, warnings: &mut Vec<Diagnostic>) -> Result<Vec<AST>, Diagnostic> {
     let mut parser = Parser {
         pos: 0,
-        tokens: tokens,
+        tokens,
         offset: 0,
-        locals: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        tags: vec![HashMap::new()],
+        warnings: Vec::new(),
     };
-    parser.program()
+    let ret = parser.program();
+    warnings.append(&mut parser.warnings);
+    ret
 }
 
 impl Parser {
@@ -129,52 +686,60 @@ impl Parser {
 
     // check if the current token matches the token with the specified string
     fn is(&self, string: &str) -> bool {
-        match self.cur_token() {
-            Token { string: t, .. } if t == string => true,
-            _ => false,
-        }
+        self.cur_token().string == string
     }
 
     fn is_eof(&self) -> bool {
-        match self.cur_token() {
-            Token {
-                kind: TokenKind::Eof,
-                ..
-            } => true,
-            _ => false,
-        }
+        matches!(self.cur_token().kind, TokenKind::Eof)
     }
 
     fn is_num(&self) -> bool {
-        match self.cur_token() {
-            Token {
-                kind: TokenKind::Num(_),
-                ..
-            } => true,
-            _ => false,
-        }
+        matches!(self.cur_token().kind, TokenKind::Num(..))
     }
 
+    fn is_str(&self) -> bool {
+        matches!(self.cur_token().kind, TokenKind::Str(_))
+    }
+
+    // the variables followed by "(" are called through the pointer in postfix
     fn is_funccall(&self) -> bool {
-        if self.tokens[self.pos + 1].string.clone() == "(" {
-            return true;
-        } else {
-            return false;
-        }
+        let is_paren = matches!(self.tokens.get(self.pos + 1), Some(t) if t.string == "(");
+        let var = self.find_var(&self.cur_token().string);
+        is_paren && matches!(var, Some(VarScope::Func(..)) | None)
     }
 
     fn is_declspec(&self) -> bool {
-        if self.is("int") {
-            return true;
-        } else {
-            return false;
+        self.is_typename(&self.cur_token())
+    }
+
+    // whether the token begins a type
+    // an identifier is a type only if it is declared by typedef (ex: T * x; is a declaration)
+    fn is_typename(&self, token: &Token) -> bool {
+        let s = token.string.as_str();
+        match token.kind {
+            TokenKind::Ident => matches!(self.find_var(s), Some(VarScope::Typedef(..))),
+            TokenKind::Keyword => {
+                TYPE_SPECIFIERS.contains(&s) || s == "struct" || s == "union" || s == "enum"
+            }
+            _ => false,
+        }
+    }
+
+    // span from start to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        match self.pos {
+            0 => start,
+            _ => start.to(self.tokens[self.pos - 1].span),
         }
     }
 
     // read forward the current token and return it
     fn consume_any(&mut self) -> Token {
         let ret = self.cur_token();
-        self.pos += 1;
+        // stay at Eof so that the errors can point at it
+        if !self.is_eof() {
+            self.pos += 1;
+        }
         //d
         //println!("consumed index: {}, Token: {:?}", self.pos, ret);
         ret
@@ -185,36 +750,274 @@ impl Parser {
     fn consume(&mut self, string: &str) -> bool {
         if self.is(string) {
             self.consume_any();
-            return true;
+            true
         } else {
-            return false;
+            false
         }
     }
 
     // read forward the expected token
-    fn expected(&mut self, string: &str) {
+    fn expected(&mut self, string: &str) -> Result<(), Diagnostic> {
+        if self.consume(string) {
+            return Ok(());
+        }
+        let msg = format!("expected '{}'", string);
+        // a missing ';' is reported just after the previous token like clang
+        if string == ";" && self.pos > 0 {
+            let span = self.tokens[self.pos - 1].span.end_point();
+            let diag = Diagnostic::new(DiagnosticKind::Syntax, "expected-token", &msg, Some(span));
+            return Err(diag.with_fixit(span, ";"));
+        }
+        Err(self.error_at("expected-token", &msg))
+    }
+
+    // read forward the closing bracket
+    // if it is missing, the error points at the opening one as well
+    fn expected_closing(&mut self, string: &str, open: Span) -> Result<(), Diagnostic> {
         if !self.consume(string) {
-            self.error_at("unexpected token");
+            let msg = format!("expected '{}'", string);
+            let opening = match string {
+                ")" => "(",
+                "]" => "[",
+                _ => "{",
+            };
+            let note = format!("to match this '{}'", opening);
+            let fixit = self.cur_token().span.start_point();
+            return Err(self
+                .error_at("expected-token", &msg)
+                .with_note(&note, open)
+                .with_fixit(fixit, string));
         }
+        Ok(())
     }
 
     // 現在のトークンはNumトークンであり、それを読み進めて返す
-    fn consume_number(&mut self) -> isize {
-        match self.consume_any() {
-            Token {
-                kind: TokenKind::Num(n),
-                ..
-            } => n,
-            _ => {
-                self.error_at("number is expected");
-                0
+    fn consume_number(&mut self) -> Result<(i64, Type), Diagnostic> {
+        match self.cur_token().kind {
+            TokenKind::Num(val, ty) => {
+                self.consume_any();
+                Ok((val, ty))
+            }
+            _ => Err(self.error_at("expected-number", "number is expected")),
+        }
+    }
+
+    // warn if the body of if/while/for is just ';' on the same line as ')',
+    // which is likely a mistake like `if (x);`
+    fn check_empty_body(&mut self, stmt: &str) {
+        let close = self.tokens[self.pos - 1].span;
+        let semi = self.cur_token().span;
+        if self.is(";") && semi.line == close.line {
+            let msg = format!("{} statement has empty body", stmt);
+            let warning = Diagnostic::warning(DiagnosticKind::Semantic, "empty-body", &msg, semi)
+                .with_note(
+                    "put the semicolon on a separate line to silence this warning",
+                    semi,
+                );
+            self.warnings.push(warning);
+        }
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.tags.push(HashMap::new());
+    }
+
+    fn leave_scope(&mut self) {
+        self.scopes.pop();
+        self.tags.pop();
+    }
+
+    // find the struct tag from the innermost scope
+    fn find_tag(&self, tag: &str) -> Option<&Tag> {
+        self.tags.iter().rev().find_map(|scope| scope.get(tag))
+    }
+
+    // find the variable from the innermost scope
+    fn find_var(&self, name: &str) -> Option<&VarScope> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // the variables must have the size
+    fn check_complete(&self, ident: &Token, ty: &Type) -> Result<(), Diagnostic> {
+        let msg = match ty {
+            Type::Array(_, 0) => String::from(
+                "definition of variable with array type needs an explicit size or an initializer",
+            ),
+            ty if ty.is_incomplete() => format!("variable has incomplete type '{}'", ty),
+            _ => return Ok(()),
+        };
+        Err(Diagnostic::new(
+            DiagnosticKind::Semantic,
+            "array-size-missing",
+            &msg,
+            Some(ident.span),
+        ))
+    }
+
+    // declare a typedef name in the innermost scope
+    // the same typedef can be repeated with the same type
+    fn declare_typedef(&mut self, ident: &Token, ty: Type) -> Result<(), Diagnostic> {
+        let scope = self.scopes.last_mut().unwrap();
+        let (code, msg) = match scope.get(&ident.string) {
+            Some(VarScope::Typedef(prev_ty, _)) if *prev_ty == ty => return Ok(()),
+            Some(VarScope::Typedef(prev_ty, _)) => (
+                "typedef-redefinition",
+                format!(
+                    "typedef redefinition with different types ('{}' vs '{}')",
+                    ty, prev_ty
+                ),
+            ),
+            Some(_) => (
+                "redefinition-different-kind",
+                format!(
+                    "redefinition of '{}' as different kind of symbol",
+                    ident.string
+                ),
+            ),
+            None => {
+                let var = VarScope::Typedef(ty, ident.span);
+                scope.insert(ident.string.clone(), var);
+                return Ok(());
             }
+        };
+        let prev = scope[&ident.string].span();
+        let diag = Diagnostic::new(DiagnosticKind::Semantic, code, &msg, Some(ident.span));
+        Err(diag.with_note("previous definition is here", prev))
+    }
+
+    // declare a local variable in the innermost scope with its own storage
+    // and return the offset from RBP
+    fn declare_local(&mut self, ident: &Token, ty: &Type) -> Result<usize, Diagnostic> {
+        if let Some(prev) = self.scopes.last().unwrap().get(&ident.string) {
+            let msg = format!("redefinition of '{}'", ident.string);
+            let diag = Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "redefinition",
+                &msg,
+                Some(ident.span),
+            );
+            return Err(diag.with_note("previous definition is here", prev.span()));
         }
+        self.check_complete(ident, ty)?;
+        // the variables are placed below RBP with their natural alignment
+        self.offset = align_to(self.offset + ty.size(), ty.align());
+        let var = VarScope::Local(self.offset, ty.clone(), ident.span);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(ident.string.clone(), var);
+        Ok(self.offset)
+    }
+
+    // declare the global variable defined elsewhere in the innermost scope (ex: { extern int x; })
+    fn declare_extern(&mut self, ident: &Token, ty: Type) -> Result<(), Diagnostic> {
+        if let Some(prev) = self.scopes.last().unwrap().get(&ident.string) {
+            let msg = format!("redefinition of '{}'", ident.string);
+            let diag = Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "redefinition",
+                &msg,
+                Some(ident.span),
+            );
+            return Err(diag.with_note("previous definition is here", prev.span()));
+        }
+        // it refers to the same variable as the file scope one
+        if let Some(VarScope::Global(prev_ty, prev_span, _)) = self.scopes[0].get(&ident.string) {
+            if *prev_ty != ty {
+                let msg = format!("conflicting types for '{}'", ident.string);
+                let diag = Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "conflicting-types",
+                    &msg,
+                    Some(ident.span),
+                );
+                return Err(diag.with_note("previous declaration is here", *prev_span));
+            }
+        }
+        if self.is("=") {
+            let msg =
+                "declaration of block scope identifier with linkage cannot have an initializer";
+            let diag = Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "extern-initializer",
+                msg,
+                Some(ident.span),
+            );
+            return Err(diag);
+        }
+        let var = VarScope::Global(ty, ident.span, false);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(ident.string.clone(), var);
+        Ok(())
+    }
+
+    // declare the function in the file scope
+    // it can be declared many times, but must agree with the previous declarations
+    fn declare_func(&mut self, ident: &Token, ty: Type, has_body: bool) -> Result<(), Diagnostic> {
+        let name = &ident.string;
+        let error = |code: &'static str, msg: String, note: &str, prev: Span| {
+            let diag = Diagnostic::new(DiagnosticKind::Semantic, code, &msg, Some(ident.span));
+            diag.with_note(note, prev)
+        };
+        let mut ty = ty;
+        let mut span = ident.span;
+        let mut is_defined = has_body;
+        match self.scopes[0].get(name) {
+            Some(VarScope::Func(prev_ty, prev_span, prev_defined)) => {
+                if !prev_ty.is_compatible_func(&ty) {
+                    let msg = format!("conflicting types for '{}'", name);
+                    return Err(error(
+                        "conflicting-types",
+                        msg,
+                        "previous declaration is here",
+                        *prev_span,
+                    ));
+                }
+                if *prev_defined && has_body {
+                    let msg = format!("redefinition of '{}'", name);
+                    return Err(error(
+                        "redefinition",
+                        msg,
+                        "previous definition is here",
+                        *prev_span,
+                    ));
+                }
+                // keep the prototype and the location of the definition
+                if let (
+                    Type::Func { params: None, .. },
+                    Type::Func {
+                        params: Some(_), ..
+                    },
+                ) = (&ty, prev_ty)
+                {
+                    ty = prev_ty.clone();
+                }
+                if *prev_defined {
+                    span = *prev_span;
+                    is_defined = true;
+                }
+            }
+            Some(prev) => {
+                let msg = format!("redefinition of '{}' as different kind of symbol", name);
+                return Err(error(
+                    "redefinition-different-kind",
+                    msg,
+                    "previous definition is here",
+                    prev.span(),
+                ));
+            }
+            None => (),
+        }
+        self.scopes[0].insert(name.clone(), VarScope::Func(ty, span, is_defined));
+        Ok(())
+    }
+
+    // syntax error at the current token
+    fn error_at(&self, code: &'static str, string: &str) -> Diagnostic {
+        self.error_kind_at(DiagnosticKind::Syntax, code, string)
     }
 
-    fn error_at(&self, string: &str) {
-        println!("{}", string);
-        panic!("error! pos: {}, token: {:?}", self.pos, self.cur_token());
+    fn error_kind_at(&self, kind: DiagnosticKind, code: &'static str, string: &str) -> Diagnostic {
+        Diagnostic::new(kind, code, string, Some(self.cur_token().span))
     }
 
     // ----- Description of grammar by EBNF -----
@@ -231,73 +1034,380 @@ impl Parser {
         ret
     }*/
 
-    // program = func_decl*
-    fn program(&mut self) -> Vec<AST> {
+    // program = (func_decl | global_decl)*
+    fn program(&mut self) -> Result<Vec<AST>, Diagnostic> {
         let mut ret = Vec::new();
         loop {
             //println!("statement[{}]", i);
             if self.is_eof() {
                 break;
             }
-            ret.push(self.func_decl());
+            if self.is_function() {
+                if let Some(func) = self.func_decl()? {
+                    ret.push(func);
+                }
+                continue;
+            }
+            // a variable is emitted once even if it is declared many times,
+            // and the definition with an initializer wins
+            for decl in self.global_decl()? {
+                let (name, has_init) = match decl.kind() {
+                    NodeKind::GVarDecl { name, init, .. } => (name, init.is_some()),
+                    _ => unreachable!(),
+                };
+                let prev = ret.iter().position(
+                    |ast| matches!(ast.kind(), NodeKind::GVarDecl { name: n, .. } if n == name),
+                );
+                match prev {
+                    Some(i) if has_init => ret[i] = decl,
+                    Some(_) => (),
+                    None => ret.push(decl),
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    // look ahead whether the declaration at the current position is a function
+    fn is_function(&self) -> bool {
+        let mut pos = self.pos;
+        let mut has_type = false;
+        while let Some(token) = self.tokens.get(pos) {
+            // the typedef name before any type is the type (ex: T f(void))
+            if token.kind == TokenKind::Ident && !has_type && self.is_typename(token) {
+                has_type = true;
+                pos += 1;
+                continue;
+            }
+            if token.kind == TokenKind::Ident {
+                return matches!(self.tokens.get(pos + 1), Some(t) if t.string == "(");
+            }
+            if token.kind == TokenKind::Eof
+                || token.string == ";"
+                || token.string == "="
+                || token.string == "typedef"
+            {
+                return false;
+            }
+            has_type |= self.is_typename(token);
+            // skip the tag and the members of struct (and the enumerators of enum)
+            if token.string == "struct" || token.string == "union" || token.string == "enum" {
+                pos += 1;
+                if matches!(self.tokens.get(pos), Some(t) if t.kind == TokenKind::Ident) {
+                    pos += 1;
+                }
+                if matches!(self.tokens.get(pos), Some(t) if t.string == "{") {
+                    let mut depth = 0;
+                    while let Some(t) = self.tokens.get(pos) {
+                        match t.string.as_str() {
+                            "{" => depth += 1,
+                            "}" => depth -= 1,
+                            _ if t.kind == TokenKind::Eof => return false,
+                            _ => (),
+                        }
+                        pos += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+            pos += 1;
+        }
+        false
+    }
+
+    // global_decl = "extern"? declspec declarator ("=" initializer)? ("," declarator ("=" initializer)?)* ";"
+    //             | typedef ";"
+    fn global_decl(&mut self) -> Result<Vec<AST>, Diagnostic> {
+        let mut ret = Vec::new();
+        if self.is("typedef") {
+            self.typedef()?;
+            self.expected(";")?;
+            return Ok(ret);
+        }
+        let is_extern = self.consume("extern");
+        let declspec = self.declspec()?;
+        // only declares the struct tag (ex: struct point { int x, y; };)
+        if self.consume(";") {
+            return Ok(ret);
+        }
+
+        while {
+            let (ident, ty) = self.declarator(declspec.clone())?;
+            // declaration of a function by the typedef name (ex: F f;)
+            if let Type::Func { .. } = ty {
+                self.declare_func(&ident, ty, false)?;
+            } else if let Some(decl) = self.global_var(ident, ty, is_extern)? {
+                ret.push(decl);
+            }
+            self.consume(",") // loop only while this is met
+        } {}
+        self.expected(";")?;
+        Ok(ret)
+    }
+
+    // declare the global variable with the initializer ("=" initializer)?
+    // returns the definition (None for extern declarations)
+    fn global_var(
+        &mut self,
+        ident: Token,
+        ty: Type,
+        is_extern: bool,
+    ) -> Result<Option<AST>, Diagnostic> {
+        if !is_extern {
+            self.check_complete(&ident, &ty)?;
+        }
+        let name = ident.string;
+        let init = if self.consume("=") {
+            let ast = self.constant_expr()?;
+            Some(self.global_init(&ast, &ty)?)
+        } else {
+            None
+        };
+
+        if let Some(prev @ (VarScope::Func(..) | VarScope::Enum(..) | VarScope::Typedef(..))) =
+            self.scopes[0].get(&name)
+        {
+            let msg = format!("redefinition of '{}' as different kind of symbol", name);
+            let diag = Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "redefinition-different-kind",
+                &msg,
+                Some(ident.span),
+            );
+            return Err(diag.with_note("previous definition is here", prev.span()));
+        }
+        // the same variable can be declared many times, but defined once
+        if let Some(VarScope::Global(prev_ty, prev_span, prev_init)) = self.scopes[0].get(&name) {
+            if *prev_ty != ty {
+                let msg = format!("conflicting types for '{}'", name);
+                let diag = Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "conflicting-types",
+                    &msg,
+                    Some(ident.span),
+                );
+                return Err(diag.with_note("previous declaration is here", *prev_span));
+            }
+            if *prev_init && init.is_some() {
+                let msg = format!("redefinition of '{}'", name);
+                let diag = Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "redefinition",
+                    &msg,
+                    Some(ident.span),
+                );
+                return Err(diag.with_note("previous definition is here", *prev_span));
+            }
+        }
+        let was_defined = matches!(
+            self.scopes[0].get(&name),
+            Some(VarScope::Global(_, _, true))
+        );
+        if !was_defined {
+            let var = VarScope::Global(ty.clone(), ident.span, init.is_some());
+            self.scopes[0].insert(name.clone(), var);
+        }
+        // extern declarations only refer to the variable defined elsewhere
+        if init.is_some() || !is_extern {
+            let kind = NodeKind::GVarDecl { name, ty, init };
+            return Ok(Some(new_node(kind, ident.span)));
+        }
+        Ok(None)
+    }
+
+    // initializer of a global variable, which must be a constant
+    fn global_init(&self, ast: &AST, ty: &Type) -> Result<GInit, Diagnostic> {
+        let aggregate = match ty {
+            Type::Array(..) => "an array",
+            Type::Struct(_) => "a struct",
+            _ => "",
+        };
+        if !aggregate.is_empty() {
+            let msg = format!("initializing {} is not supported", aggregate);
+            return Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "unsupported-initializer",
+                &msg,
+                Some(ast.span()),
+            ));
+        }
+        match (ast.kind(), ty) {
+            (NodeKind::Str(s), Type::Ptr(_)) => return Ok(GInit::Str(s)),
+            (NodeKind::Addr(var), Type::Ptr(_)) => {
+                if let NodeKind::GVar { name, .. } = var.kind() {
+                    return Ok(GInit::Addr(name));
+                }
+            }
+            // the function is converted to its address
+            (
+                NodeKind::GVar {
+                    name,
+                    ty: Type::Func { .. },
+                },
+                Type::Ptr(_),
+            ) => return Ok(GInit::Addr(name)),
+            _ => (),
+        }
+        match sema::eval(ast) {
+            Some(val) => Ok(GInit::Int(val)),
+            None => Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "non-constant-initializer",
+                "initializer element is not a compile-time constant",
+                Some(ast.span()),
+            )),
         }
-        ret
     }
 
-    //func_decl = ident "(" ("void"|(declspec declarator) (, declspec declarator)* ) ")" { stmt* }
-    fn func_decl(&mut self) -> AST {
-        // reset the stack frame size and the local variables
+    // func_decl = "extern"? declspec "*"* ident "(" func_params ")" ("{" stmt* "}" | ";")
+    // a prototype (declaration without the body) returns None
+    fn func_decl(&mut self) -> Result<Option<AST>, Diagnostic> {
+        // reset the stack frame size
         self.offset = 0;
-        self.locals = HashMap::new();
 
         let mut args = Vec::new();
         let mut stmts = Vec::new();
 
-        let mut ret_ty = self.declspec();
+        let start = self.cur_token().span;
+        self.consume("extern");
+        let mut ret_ty = self.declspec()?;
         while self.consume("*") {
             ret_ty = Type::Ptr(Box::new(ret_ty));
         }
 
-        let func_name = self.consume_any().string;
-        self.consume("(");
-        if !self.consume("void") {
-            // do-whileをはさむ
-            while {
-                let arg_type = self.declspec();
-                let (arg_name, arg_type) = self.declarator(arg_type);
-
-                // offsetをすすめる
-                self.offset += match arg_type {
-                    Type::Int => 8,
-                    Type::Ptr(_) => 8,
-                    _ => 0,
-                };
+        let ident = self.consume_any();
+        if let Type::Struct(_) = ret_ty {
+            let msg = "returning a struct by value is not supported";
+            return Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "unsupported-struct-return",
+                msg,
+                Some(ident.span),
+            ));
+        }
+        let open = self.cur_token().span;
+        self.expected("(")?;
+        let (params, ty) = self.func_params(ret_ty.clone())?;
+        self.expected_closing(")", open)?;
 
-                // 引数リストにわたす (codegenに伝える)
-                args.push((self.offset, arg_type.clone()));
-                // ローカル変数リストにpush
-                self.locals
-                    .insert(arg_name, (self.offset, arg_type.clone()));
+        let has_body = self.is("{");
+        self.declare_func(&ident, ty, has_body)?;
+        if !has_body {
+            self.expected(";")?;
+            return Ok(None);
+        }
 
-                self.consume(",") // loop only while this is met
+        // the parameters and the body share the same scope
+        self.enter_scope();
+        for (name, ty, span) in params {
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    let msg = "parameter name omitted";
+                    return Err(Diagnostic::new(
+                        DiagnosticKind::Syntax,
+                        "parameter-name-omitted",
+                        msg,
+                        Some(span),
+                    ));
+                }
+            };
+            // 引数リストにわたす (codegenに伝える)
+            let offset = self.declare_local(&name, &ty)?;
+            args.push((offset, ty));
+        }
+
+        self.consume("{");
+        while !self.consume("}") {
+            stmts.push(self.stmt()?);
+        }
+        self.leave_scope();
+
+        Ok(Some(new_node(
+            NodeKind::FuncDecl {
+                name: ident.string,
+                args,
+                ret_type: ret_ty,
+                // RSP stays aligned to 16 bytes
+                frame_size: align_to(self.offset, 16),
+                stmts,
+            },
+            self.span_from(start),
+        )))
+    }
+
+    // func_params = "void" | ε | param ("," param)* ("," "...")?
+    // param = declspec declarator
+    // returns the parameters (name, type, location) and the type of the function
+    #[allow(clippy::type_complexity)]
+    fn func_params(
+        &mut self,
+        ret: Type,
+    ) -> Result<(Vec<(Option<Token>, Type, Span)>, Type), Diagnostic> {
+        let mut params = Vec::new();
+        let mut is_variadic = false;
+        let ret = Box::new(ret);
+
+        // f() has no prototype
+        if self.is(")") {
+            let ty = Type::Func {
+                ret,
+                params: None,
+                is_variadic,
+            };
+            return Ok((params, ty));
+        }
+        let is_void = matches!(self.tokens.get(self.pos + 1), Some(t) if t.string == ")");
+        if !(self.is("void") && is_void) {
+            // do-whileをはさむ
+            while {
+                if self.consume("...") {
+                    is_variadic = true;
+                    false
+                } else {
+                    let start = self.cur_token().span;
+                    let declspec = self.declspec()?;
+                    let (name, ty) = self.declarator_opt(declspec)?;
+                    if ty == Type::Void {
+                        let msg = "Type void must not be a variable type";
+                        let span = self.span_from(start);
+                        return Err(Diagnostic::new(
+                            DiagnosticKind::Semantic,
+                            "void-variable",
+                            msg,
+                            Some(span),
+                        ));
+                    }
+                    // the parameter of array type is a pointer (the first size is ignored),
+                    // and that of function type is a function pointer
+                    let ty = ty.decay();
+                    if let Type::Struct(_) = ty {
+                        let msg = "passing a struct by value is not supported";
+                        let span = self.span_from(start);
+                        return Err(Diagnostic::new(
+                            DiagnosticKind::Semantic,
+                            "unsupported-struct-argument",
+                            msg,
+                            Some(span),
+                        ));
+                    }
+                    params.push((name, ty, self.span_from(start)));
+                    self.consume(",") // loop only while this is met
+                }
             } {}
+        } else {
+            self.consume("void");
         }
 
-        self.consume(")");
-        self.consume("{");
-        while !self.consume("}") {
-            stmts.push(self.stmt());
-        }
-
-        AST::Node {
-            kind: NodeKind::FuncDecl {
-                name: func_name,
-                args: Box::new(args),
-                ret_type: ret_ty,
-                frame_size: self.offset,
-                stmts: Box::new(stmts),
-            },
-        }
+        let ty = Type::Func {
+            ret,
+            params: Some(params.iter().map(|(_, ty, _)| ty.clone()).collect()),
+            is_variadic,
+        };
+        Ok((params, ty))
     }
 
     // stmt = expr ";"
@@ -307,393 +1417,1291 @@ impl Parser {
     //      | "return" ;
     //      | "if" "(" expr ")" stmt ("else" stmt)?
     //      | "while" "(" expr ")" stmt
-    //      | "for" "(" expr? ";" expr? ";" expr? ")" stmt
-    // Todo declarationを式として評価したい(ex: for(int i;;) )
-    fn stmt(&mut self) -> AST {
+    //      | "for" "(" (declaration ";" | expr? ";") expr? ";" expr? ")" stmt
+    fn stmt(&mut self) -> Result<AST, Diagnostic> {
+        let start = self.cur_token().span;
         // "return" expr ";"
         if self.consume("return") {
-            let ast;
-            if self.is(";") {
-                ast = AST::Node {
-                    kind: NodeKind::Return(Box::new(AST::Nil)),
-                };
-            } else {
-                ast = AST::Node {
-                    kind: NodeKind::Return(Box::new(self.expr())),
-                };
-            }
-            self.expected(";");
-            return ast;
+            let expr = if self.is(";") { AST::Nil } else { self.expr()? };
+            self.expected(";")?;
+            Ok(new_node(
+                NodeKind::Return(Box::new(expr)),
+                self.span_from(start),
+            ))
         }
         // "if" "(" expr ")" stmt ("else" stmt)?
         else if self.consume("if") {
-            self.expected("(");
-            let cond = self.expr();
-            self.expected(")");
-            let then = self.stmt();
+            let open = self.cur_token().span;
+            self.expected("(")?;
+            let cond = self.expr()?;
+            self.expected_closing(")", open)?;
+            self.check_empty_body("if");
+            let then = self.stmt()?;
             let mut els = AST::Nil;
             if self.consume("else") {
-                els = self.stmt();
+                els = self.stmt()?;
             }
-            return AST::Node {
-                kind: NodeKind::If {
+            Ok(new_node(
+                NodeKind::If {
                     cond: Box::new(cond),
                     then: Box::new(then),
                     els: Box::new(els),
                 },
-            };
+                self.span_from(start),
+            ))
         }
         // "while" "(" expr ")" stmt
         else if self.consume("while") {
-            self.expected("(");
-            let cond = self.expr();
-            self.expected(")");
-            let proc = self.stmt();
-            return AST::Node {
-                kind: NodeKind::For {
+            let open = self.cur_token().span;
+            self.expected("(")?;
+            let cond = self.expr()?;
+            self.expected_closing(")", open)?;
+            self.check_empty_body("while");
+            let proc = self.stmt()?;
+            Ok(new_node(
+                NodeKind::For {
                     a: Box::new(AST::Nil),
                     b: Box::new(cond),
                     c: Box::new(AST::Nil),
                     proc: Box::new(proc),
                 },
-            };
+                self.span_from(start),
+            ))
         }
-        // "for" "(" expr-stmt? ";" expr? ";" expr? ")" stmt
+        // "for" "(" (declaration ";" | expr-stmt) expr? ";" expr? ")" stmt
         else if self.consume("for") {
-            self.expected("(");
-            let expr_a = self.expr_stmt();
-            let expr_b = self.expr();
-            self.consume(";");
-            let expr_c = self.expr();
-            self.expected(")");
-            let proc = self.stmt();
-            return AST::Node {
-                kind: NodeKind::For {
+            let open = self.cur_token().span;
+            self.expected("(")?;
+            // the variables declared in the first clause live only in the loop
+            self.enter_scope();
+            let expr_a = if self.is_declspec() {
+                let ast = self.declaration()?;
+                self.expected(";")?;
+                ast
+            } else {
+                self.expr_stmt()?
+            };
+            let expr_b = self.expr()?;
+            self.expected(";")?;
+            let expr_c = if self.is(")") { AST::Nil } else { self.expr()? };
+            self.expected_closing(")", open)?;
+            self.check_empty_body("for");
+            let proc = self.stmt()?;
+            self.leave_scope();
+            Ok(new_node(
+                NodeKind::For {
                     a: Box::new(expr_a),
                     b: Box::new(expr_b),
                     c: Box::new(expr_c),
                     proc: Box::new(proc),
                 },
-            };
+                self.span_from(start),
+            ))
         }
         // "{" stmt* "}"
         else if self.consume("{") {
             let mut vec = Vec::new();
+            self.enter_scope();
             while !self.consume("}") {
-                vec.push(self.stmt());
+                vec.push(self.stmt()?);
             }
-            return AST::Node {
-                kind: NodeKind::Block(Box::new(vec)),
-            };
-        } else if self.is_declspec() {
-            let ast = self.declaration();
-            self.expected(";");
-            return ast;
+            self.leave_scope();
+            Ok(new_node(NodeKind::Block(vec), self.span_from(start)))
+        } else if self.is_declspec() || self.is("typedef") || self.is("extern") {
+            let ast = self.declaration()?;
+            self.expected(";")?;
+            Ok(ast)
         }
         // expr ";"
         else {
-            return self.expr_stmt();
+            self.expr_stmt()
         }
     }
 
     // expr-stmt = expr ";"
-    fn expr_stmt(&mut self) -> AST {
-        let expr = self.expr();
-        self.expected(";");
-        AST::Node {
-            kind: NodeKind::ExprStmt(Box::new(expr)),
-        }
+    fn expr_stmt(&mut self) -> Result<AST, Diagnostic> {
+        let start = self.cur_token().span;
+        let expr = self.expr()?;
+        self.expected(";")?;
+        Ok(new_node(
+            NodeKind::ExprStmt(Box::new(expr)),
+            self.span_from(start),
+        ))
     }
 
     // expr = assign
     //      | blank expression (OK only if the current token matches to ";")
-    fn expr(&mut self) -> AST {
+    fn expr(&mut self) -> Result<AST, Diagnostic> {
         if self.is(";") {
-            return AST::Nil;
+            return Ok(AST::Nil);
         }
         self.assign()
     }
 
-    // assign = equality ("=" assign)?
-    fn assign(&mut self) -> AST {
-        let mut ast = self.equality();
+    // assign = logor (assign-op assign)?
+    // assign-op = "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^=" | "<<=" | ">>="
+    fn assign(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.logor()?;
         while !self.is_eof() {
             if self.consume("=") {
-                ast = AST::Node {
-                    kind: NodeKind::Assign(Box::new(ast), Box::new(self.assign())),
-                };
+                let rhs = self.assign()?;
+                ast = new_binary(NodeKind::Assign, ast, rhs);
+            } else if let Some(op) = COMPOUND_ASSIGN_OPS.iter().find(|op| self.is(op)) {
+                self.consume_any();
+                let rhs = self.assign()?;
+                ast = self.compound_assign(op, ast, rhs);
             } else {
                 break;
             }
         }
-        ast
+        Ok(ast)
+    }
+
+    // expression evaluated at compile time, which is analyzed here
+    // since the parser needs its value (ex: the length of an array)
+    fn constant_expr(&mut self) -> Result<AST, Diagnostic> {
+        let ast = self.assign()?;
+        sema::analyze_expr(ast, &mut self.warnings)
+    }
+
+    // type of the expression which stays in the tree
+    // (its warnings are reported when the whole tree is analyzed)
+    fn type_of(&self, ast: &AST) -> Result<Type, Diagnostic> {
+        Ok(sema::analyze_expr(ast.clone(), &mut Vec::new())?.ty())
+    }
+
+    // A op= B is A = A op B, but A is evaluated only once
+    // A is accessed through a temporary pointer if it has side effects (ex: a[i++] += 2)
+    fn compound_assign(&mut self, op: &str, lhs: AST, rhs: AST) -> AST {
+        let span = lhs.span().to(rhs.span());
+        let tmp = if has_side_effects(&lhs) {
+            Some(self.new_temp())
+        } else {
+            None
+        };
+        let value = match op {
+            "+=" => new_binary(NodeKind::Plus, lhs, rhs),
+            "-=" => new_binary(NodeKind::Minus, lhs, rhs),
+            "*=" => new_binary(NodeKind::Mul, lhs, rhs),
+            "/=" => new_binary(NodeKind::Div, lhs, rhs),
+            "%=" => new_binary(NodeKind::Mod, lhs, rhs),
+            "&=" => new_binary(NodeKind::BitAnd, lhs, rhs),
+            "|=" => new_binary(NodeKind::BitOr, lhs, rhs),
+            "^=" => new_binary(NodeKind::BitXor, lhs, rhs),
+            "<<=" => new_binary(NodeKind::Shl, lhs, rhs),
+            ">>=" => new_binary(NodeKind::Shr, lhs, rhs),
+            _ => panic!("unknown compound assignment"),
+        };
+        new_node(NodeKind::OpAssign(Box::new(value), tmp), span)
+    }
+
+    // A++ is made of A += 1 (A-- likewise), and sema gives back the old value
+    fn postfix_incdec(&mut self, lhs: AST, op: &Token, start: Span) -> AST {
+        let one = new_node_num(1, op.span);
+        let span = self.span_from(start);
+        if op.string == "++" {
+            let update = self.compound_assign("+=", lhs, one);
+            new_node(NodeKind::PostInc(Box::new(update)), span)
+        } else {
+            let update = self.compound_assign("-=", lhs, one);
+            new_node(NodeKind::PostDec(Box::new(update)), span)
+        }
+    }
+
+    // offset of an unnamed local pointer used to evaluate an lvalue only once
+    fn new_temp(&mut self) -> usize {
+        self.offset = align_to(self.offset + 8, 8);
+        self.offset
+    }
+
+    // logor = logand ("||" logand)*
+    fn logor(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.logand()?;
+        while self.consume("||") {
+            let rhs = self.logand()?;
+            ast = new_binary(NodeKind::LogOr, ast, rhs);
+        }
+        Ok(ast)
+    }
+
+    // logand = bitor ("&&" bitor)*
+    fn logand(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.bitor()?;
+        while self.consume("&&") {
+            let rhs = self.bitor()?;
+            ast = new_binary(NodeKind::LogAnd, ast, rhs);
+        }
+        Ok(ast)
+    }
+
+    // bitor = bitxor ("|" bitxor)*
+    fn bitor(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.bitxor()?;
+        while self.consume("|") {
+            let rhs = self.bitxor()?;
+            ast = new_binary(NodeKind::BitOr, ast, rhs);
+        }
+        Ok(ast)
+    }
+
+    // bitxor = bitand ("^" bitand)*
+    fn bitxor(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.bitand()?;
+        while self.consume("^") {
+            let rhs = self.bitand()?;
+            ast = new_binary(NodeKind::BitXor, ast, rhs);
+        }
+        Ok(ast)
+    }
+
+    // bitand = equality ("&" equality)*
+    fn bitand(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.equality()?;
+        while self.consume("&") {
+            let rhs = self.equality()?;
+            ast = new_binary(NodeKind::BitAnd, ast, rhs);
+        }
+        Ok(ast)
     }
 
     // equality = relational ("==" relational | "!=" relational)*
-    fn equality(&mut self) -> AST {
-        let mut ast = self.relational();
+    fn equality(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.relational()?;
         while !self.is_eof() {
             if self.consume("==") {
-                ast = AST::Node {
-                    kind: NodeKind::Eq(Box::new(ast), Box::new(self.relational())),
-                };
+                let rhs = self.relational()?;
+                ast = new_binary(NodeKind::Eq, ast, rhs);
             } else if self.consume("!=") {
-                ast = AST::Node {
-                    kind: NodeKind::Ne(Box::new(ast), Box::new(self.relational())),
-                };
+                let rhs = self.relational()?;
+                ast = new_binary(NodeKind::Ne, ast, rhs);
             } else {
                 break;
             }
         }
-        ast
+        Ok(ast)
     }
 
-    // relational = add ("<" add | "<=" add | ">" add | ">=" add)*
-    fn relational(&mut self) -> AST {
-        let mut ast = self.add();
+    // relational = shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
+    fn relational(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.shift()?;
 
         while !self.is_eof() {
             if self.consume("<=") {
-                ast = AST::Node {
-                    kind: NodeKind::Le(Box::new(ast), Box::new(self.add())),
-                };
+                let rhs = self.shift()?;
+                ast = new_binary(NodeKind::Le, ast, rhs);
             } else if self.consume("<") {
-                ast = AST::Node {
-                    kind: NodeKind::Lt(Box::new(ast), Box::new(self.add())),
-                };
+                let rhs = self.shift()?;
+                ast = new_binary(NodeKind::Lt, ast, rhs);
             } else if self.consume(">=") {
-                ast = AST::Node {
-                    kind: NodeKind::Le(Box::new(self.add()), Box::new(ast)),
-                };
+                // a >= b is b <= a, but the span still starts from a
+                let rhs = self.shift()?;
+                let span = ast.span().to(rhs.span());
+                ast = new_node(NodeKind::Le(Box::new(rhs), Box::new(ast)), span);
             } else if self.consume(">") {
-                ast = AST::Node {
-                    kind: NodeKind::Lt(Box::new(self.add()), Box::new(ast)),
-                };
+                let rhs = self.shift()?;
+                let span = ast.span().to(rhs.span());
+                ast = new_node(NodeKind::Lt(Box::new(rhs), Box::new(ast)), span);
+            } else {
+                break;
+            }
+        }
+        Ok(ast)
+    }
+
+    // shift = add ("<<" add | ">>" add)*
+    fn shift(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.add()?;
+
+        while !self.is_eof() {
+            if self.consume("<<") {
+                let rhs = self.add()?;
+                ast = new_binary(NodeKind::Shl, ast, rhs);
+            } else if self.consume(">>") {
+                let rhs = self.add()?;
+                ast = new_binary(NodeKind::Shr, ast, rhs);
             } else {
                 break;
             }
         }
-        ast
+        Ok(ast)
     }
 
     // add = mul ("+" mul | "-" mul)*
-    fn add(&mut self) -> AST {
-        let mut ast = self.mul();
+    fn add(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.mul()?;
 
         while !self.is_eof() {
             if self.consume("+") {
-                ast = AST::Node {
-                    kind: NodeKind::Plus(Box::new(ast), Box::new(self.mul())),
-                };
+                let rhs = self.mul()?;
+                ast = new_binary(NodeKind::Plus, ast, rhs);
             } else if self.consume("-") {
-                ast = AST::Node {
-                    kind: NodeKind::Minus(Box::new(ast), Box::new(self.mul())),
-                };
+                let rhs = self.mul()?;
+                ast = new_binary(NodeKind::Minus, ast, rhs);
             } else {
                 break;
             }
         }
-        ast
+        Ok(ast)
     }
 
-    // mul = unary ("*" unary | "/" unary)*
-    fn mul(&mut self) -> AST {
-        let mut ast = self.unary();
+    // mul = unary ("*" unary | "/" unary | "%" unary)*
+    fn mul(&mut self) -> Result<AST, Diagnostic> {
+        let mut ast = self.unary()?;
 
         while !self.is_eof() {
             if self.consume("*") {
-                ast = AST::Node {
-                    kind: NodeKind::Mul(Box::new(ast), Box::new(self.unary())),
-                };
+                let rhs = self.unary()?;
+                ast = new_binary(NodeKind::Mul, ast, rhs);
             } else if self.consume("/") {
-                ast = AST::Node {
-                    kind: NodeKind::Div(Box::new(ast), Box::new(self.unary())),
-                };
+                let rhs = self.unary()?;
+                ast = new_binary(NodeKind::Div, ast, rhs);
+            } else if self.consume("%") {
+                let rhs = self.unary()?;
+                ast = new_binary(NodeKind::Mod, ast, rhs);
             } else {
                 break;
             }
         }
-        ast
+        Ok(ast)
     }
 
-    // unary = ("+" | "-" | "*" | "&")? unary
-    //       | primary
-    fn unary(&mut self) -> AST {
-        if self.consume("+") {
-            return self.unary();
-        } else if self.consume("-") {
-            return AST::Node {
-                kind: NodeKind::Minus(Box::new(new_node_num(0)), Box::new(self.unary())),
+    // unary = ("+" | "-" | "*" | "&" | "!" | "~" | "++" | "--")? unary
+    //       | "sizeof" "(" type_name ")"
+    //       | "sizeof" unary
+    //       | "_Alignof" "(" type_name ")"
+    //       | postfix
+    fn unary(&mut self) -> Result<AST, Diagnostic> {
+        let start = self.cur_token().span;
+        if self.consume("sizeof") {
+            // the operand is not evaluated, only its type is used
+            let ty = if self.is_paren_type_name() {
+                self.paren_type_name()?
+            } else {
+                let ast = self.unary()?;
+                sema::analyze_expr(ast, &mut self.warnings)?.ty()
             };
+            let size = self.size_of(&ty, "sizeof", start)?;
+            Ok(new_node(
+                NodeKind::Num(size, Type::ULong),
+                self.span_from(start),
+            ))
+        } else if self.consume("_Alignof") {
+            if !self.is_paren_type_name() {
+                return Err(self.error_at("expected-type", "expected a type"));
+            }
+            let ty = self.paren_type_name()?;
+            self.size_of(&ty, "_Alignof", start)?;
+            let align = ty.align() as i64;
+            Ok(new_node(
+                NodeKind::Num(align, Type::ULong),
+                self.span_from(start),
+            ))
+        } else if self.is("++") || self.is("--") {
+            // ++A is A += 1, and --A is A -= 1
+            let op = self.consume_any();
+            let ast = self.unary()?;
+            let one = new_node_num(1, op.span);
+            let op = if op.string == "++" { "+=" } else { "-=" };
+            Ok(self.compound_assign(op, ast, one))
+        } else if self.consume("+") {
+            // +A is 0 + A, so A is promoted
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::Plus(Box::new(new_node_num(0, start)), Box::new(ast)),
+                self.span_from(start),
+            ))
+        } else if self.consume("-") {
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::Minus(Box::new(new_node_num(0, start)), Box::new(ast)),
+                self.span_from(start),
+            ))
         } else if self.consume("*") {
-            return AST::Node {
-                kind: NodeKind::Deref(Box::new(self.unary())),
-            };
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::Deref(Box::new(ast)),
+                self.span_from(start),
+            ))
         } else if self.consume("&") {
-            return AST::Node {
-                kind: NodeKind::Addr(Box::new(self.unary())),
-            };
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::Addr(Box::new(ast)),
+                self.span_from(start),
+            ))
+        } else if self.consume("!") {
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::Not(Box::new(ast)),
+                self.span_from(start),
+            ))
+        } else if self.consume("~") {
+            let ast = self.unary()?;
+            Ok(new_node(
+                NodeKind::BitNot(Box::new(ast)),
+                self.span_from(start),
+            ))
         } else {
-            self.primary()
+            self.postfix()
+        }
+    }
+
+    // whether "(" type_name ")" follows
+    fn is_paren_type_name(&self) -> bool {
+        let next = match self.tokens.get(self.pos + 1) {
+            Some(token) => token,
+            None => return false,
+        };
+        self.is("(") && self.is_typename(next)
+    }
+
+    fn paren_type_name(&mut self) -> Result<Type, Diagnostic> {
+        let open = self.cur_token().span;
+        self.expected("(")?;
+        let ty = self.type_name()?;
+        self.expected_closing(")", open)?;
+        Ok(ty)
+    }
+
+    // size of the complete object type as the operand of the operator
+    fn size_of(&self, ty: &Type, operator: &str, start: Span) -> Result<i64, Diagnostic> {
+        if !ty.is_incomplete() {
+            return Ok(ty.size() as i64);
+        }
+        let msg = format!(
+            "invalid application of '{}' to an incomplete type '{}'",
+            operator, ty
+        );
+        let span = self.span_from(start);
+        Err(Diagnostic::new(
+            DiagnosticKind::Semantic,
+            "sizeof-incomplete-type",
+            &msg,
+            Some(span),
+        ))
+    }
+
+    // postfix = primary ("[" expr "]" | "." ident | "->" ident | call_args | "++" | "--")*
+    fn postfix(&mut self) -> Result<AST, Diagnostic> {
+        let start = self.cur_token().span;
+        let mut ast = self.primary()?;
+        loop {
+            let open = self.cur_token().span;
+            if self.consume("[") {
+                let index = self.expr()?;
+                self.expected_closing("]", open)?;
+                let kind = NodeKind::Index(Box::new(ast), Box::new(index));
+                ast = new_node(kind, self.span_from(start));
+            } else if self.is("(") {
+                ast = self.call_ptr(ast, start)?;
+            } else if self.consume(".") {
+                ast = self.member(ast, start)?;
+            } else if self.is("++") || self.is("--") {
+                let op = self.consume_any();
+                ast = self.postfix_incdec(ast, &op, start);
+            } else if self.consume("->") {
+                // p->x is (*p).x
+                let ty = self.type_of(&ast)?.decay();
+                if let Type::Ptr(_) = ty {
+                    let span = ast.span();
+                    ast = new_node(NodeKind::Deref(Box::new(ast)), span);
+                } else {
+                    let msg = format!("member reference type '{}' is not a pointer", ty);
+                    return Err(Diagnostic::new(
+                        DiagnosticKind::Semantic,
+                        "member-reference-not-pointer",
+                        &msg,
+                        Some(ast.span()),
+                    ));
+                }
+                ast = self.member(ast, start)?;
+            } else {
+                return Ok(ast);
+            }
+        }
+    }
+
+    // member of the struct after "." or "->"
+    fn member(&mut self, base: AST, start: Span) -> Result<AST, Diagnostic> {
+        if self.cur_token().kind != TokenKind::Ident {
+            return Err(self.error_at("expected-identifier", "expected an identifier"));
         }
+        let ident = self.consume_any();
+        let ty = self.type_of(&base)?;
+        let s = match &ty {
+            Type::Struct(s) if s.is_complete() => s,
+            Type::Struct(_) => {
+                let msg = format!("incomplete definition of type '{}'", ty);
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "incomplete-type",
+                    &msg,
+                    Some(base.span()),
+                ));
+            }
+            _ => {
+                let msg = format!(
+                    "member reference base type '{}' is not a structure or union",
+                    ty
+                );
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "member-reference-not-struct",
+                    &msg,
+                    Some(base.span()),
+                ));
+            }
+        };
+        let member = match s.member(&ident.string) {
+            Some(member) => member,
+            None => {
+                let msg = format!("no member named '{}' in '{}'", ident.string, ty);
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "no-member",
+                    &msg,
+                    Some(ident.span),
+                ));
+            }
+        };
+        Ok(new_node(
+            NodeKind::Member {
+                base: Box::new(base),
+                name: ident.string,
+                offset: member.offset,
+                ty: member.ty,
+            },
+            self.span_from(start),
+        ))
     }
 
     // primary = num
+    //         | str+
     //         | "(" expr ")"
     //         | funccall
     //         | local_var
-    fn primary(&mut self) -> AST {
+    fn primary(&mut self) -> Result<AST, Diagnostic> {
         // "(" expr ")"
+        let open = self.cur_token().span;
         if self.consume("(") {
-            let ast = self.expr();
-            self.expected(")");
-            return ast;
+            let ast = self.expr()?;
+            self.expected_closing(")", open)?;
+            Ok(ast)
         }
         // num
         else if self.is_num() {
-            return new_node_num(self.consume_number());
+            let span = self.cur_token().span;
+            let (val, ty) = self.consume_number()?;
+            Ok(new_node(NodeKind::Num(val, ty), span))
+        }
+        // str+ (adjacent string literals are concatenated)
+        else if self.is_str() {
+            let start = self.cur_token().span;
+            let mut bytes = Vec::new();
+            while let TokenKind::Str(s) = self.cur_token().kind {
+                bytes.extend(s);
+                self.consume_any();
+            }
+            Ok(new_node(NodeKind::Str(bytes), self.span_from(start)))
         }
         // funccall
         else if self.is_funccall() {
-            return self.funccall();
+            self.funccall()
         }
         // ident
         else {
-            return self.local_var();
+            self.local_var()
         }
     }
 
     // local_var 最小単位
-    fn local_var(&mut self) -> AST {
-        let ident_name = self.consume_any().string;
+    fn local_var(&mut self) -> Result<AST, Diagnostic> {
+        if self.cur_token().kind != TokenKind::Ident {
+            return Err(self.error_at("expected-expression", "expected an expression"));
+        }
+        let ident = self.cur_token();
 
         // variables
-        match &self.locals.get(&ident_name) {
+        match self.find_var(&ident.string) {
             // variable names are already registered
-            Some(t) => {
-                return AST::Node {
-                    kind: NodeKind::Var {
-                        name: ident_name.clone(),
-                        offset: t.0,
-                        ty: t.1.clone(),
+            Some(VarScope::Local(offset, ty, _)) => {
+                let ast = new_node(
+                    NodeKind::Var {
+                        name: ident.string.clone(),
+                        offset: *offset,
+                        ty: ty.clone(),
                     },
-                };
+                    ident.span,
+                );
+                self.consume_any();
+                Ok(ast)
+            }
+            Some(VarScope::Global(ty, _, _)) => {
+                let ast = new_node(
+                    NodeKind::GVar {
+                        name: ident.string.clone(),
+                        ty: ty.clone(),
+                    },
+                    ident.span,
+                );
+                self.consume_any();
+                Ok(ast)
+            }
+            // enumerators are the constants of int
+            Some(VarScope::Enum(val, _)) => {
+                let ast = new_node(NodeKind::Num(*val, Type::Int), ident.span);
+                self.consume_any();
+                Ok(ast)
+            }
+            // function designators are addressed like the global variables
+            Some(VarScope::Func(ty, _, _)) => {
+                let ast = new_node(
+                    NodeKind::GVar {
+                        name: ident.string.clone(),
+                        ty: ty.clone(),
+                    },
+                    ident.span,
+                );
+                self.consume_any();
+                Ok(ast)
             }
+            Some(VarScope::Typedef(..)) => Err(self.error_at(
+                "unexpected-type-name",
+                &format!(
+                    "unexpected type name '{}': expected expression",
+                    ident.string
+                ),
+            )),
             // not registered
+            None => Err(self.error_kind_at(
+                DiagnosticKind::Semantic,
+                "undeclared-identifier",
+                &format!("'{}' is not defined", ident.string),
+            )),
+        }
+    }
+
+    // funccall = ident<Token> call_args
+    fn funccall(&mut self) -> Result<AST, Diagnostic> {
+        let start = self.cur_token().span;
+        let ident = self.consume_any();
+        let argv = self.call_args()?;
+        let span = self.span_from(start);
+
+        let (ty, decl) = match self.find_var(&ident.string).cloned() {
+            Some(VarScope::Func(ty, decl, _)) => (ty, decl),
+            // the other names are not called here (see is_funccall)
+            Some(_) => unreachable!(),
+            // the function defined elsewhere is implicitly declared as int f()
             None => {
-                panic!("{} is not defined", ident_name.clone());
+                let msg = format!(
+                    "implicit declaration of function '{}' is invalid in C99",
+                    ident.string
+                );
+                self.warnings.push(Diagnostic::warning(
+                    DiagnosticKind::Semantic,
+                    "implicit-function-declaration",
+                    &msg,
+                    ident.span,
+                ));
+                let ty = Type::Func {
+                    ret: Box::new(Type::Int),
+                    params: None,
+                    is_variadic: false,
+                };
+                self.declare_func(&ident, ty.clone(), false)?;
+                (ty, ident.span)
             }
         };
-    }
 
-    // funccall = ident<Token> "(" (expr ",")* ")"
-    fn funccall(&mut self) -> AST {
-        let mut argv: Vec<AST> = Vec::new();
-        let func_name = self.consume_any().string;
-        self.expected("(");
+        Ok(new_node(
+            NodeKind::FuncCall {
+                name: ident.string,
+                argv,
+                ty,
+                decl,
+            },
+            span,
+        ))
+    }
 
+    // call_args = "(" (assign ("," assign)*)? ")"
+    fn call_args(&mut self) -> Result<Vec<AST>, Diagnostic> {
+        let mut argv = Vec::new();
+        let open = self.cur_token().span;
+        self.expected("(")?;
         loop {
             if self.is(")") {
                 break;
             }
-            argv.push(self.expr());
+            argv.push(self.expr()?);
             if !self.consume(",") {
                 break;
             }
         }
-        self.expected(")");
+        self.expected_closing(")", open)?;
+        Ok(argv)
+    }
+
+    // call through the function pointer (ex: fp(1), (*fp)(1), ops.add(1, 2))
+    fn call_ptr(&mut self, callee: AST, start: Span) -> Result<AST, Diagnostic> {
+        let argv = self.call_args()?;
+        Ok(new_node(
+            NodeKind::FuncPtrCall {
+                callee: Box::new(callee),
+                argv,
+            },
+            self.span_from(start),
+        ))
+    }
+
+    // declspec = ("void" | "char" | "short" | "int" | "long" | "signed" | "unsigned")+
+    //          | struct_decl
+    //          | enum_decl
+    //          | typedef_name
+    // the specifiers can be in any order (ex: long unsigned int)
+    fn declspec(&mut self) -> Result<Type, Diagnostic> {
+        if !self.is_declspec() {
+            return Err(self.error_at("expected-type", "unexpected type"));
+        }
+        let mut specs = TypeSpecifiers::default();
+        let mut tagged_ty = None;
+        let mut count = 0;
+        while self.is_declspec() {
+            let token = self.cur_token();
+            // the typedef name after the type is the declared name (ex: int T;)
+            if token.kind == TokenKind::Ident && count > 0 {
+                break;
+            }
+            let is_tagged = token.kind == TokenKind::Ident
+                || matches!(token.string.as_str(), "struct" | "union" | "enum");
+            if !is_tagged {
+                specs.count(&token.string);
+            }
+            if tagged_ty.is_some() || (is_tagged && count > 0) || specs.ty().is_none() {
+                let msg = format!(
+                    "'{}' cannot be combined with the previous declaration specifiers",
+                    token.string
+                );
+                return Err(self.error_at("invalid-specifier-combination", &msg));
+            }
+            if let Some(VarScope::Typedef(ty, _)) = self.find_var(&token.string) {
+                tagged_ty = Some(ty.clone());
+                self.consume_any();
+            } else if token.string == "enum" {
+                tagged_ty = Some(self.enum_decl()?);
+            } else if is_tagged {
+                tagged_ty = Some(self.struct_decl()?);
+            } else {
+                self.consume_any();
+            }
+            count += 1;
+        }
+        match tagged_ty {
+            Some(ty) => Ok(ty),
+            None => Ok(specs.ty().unwrap()),
+        }
+    }
+
+    // enum_decl = "enum" ident? ("{" enumerator ("," enumerator)* ","? "}")?
+    // enumerator = ident ("=" assign)?
+    // enum is int, and the enumerators are the constants of int
+    fn enum_decl(&mut self) -> Result<Type, Diagnostic> {
+        self.consume("enum");
+        let tag = match self.cur_token().kind {
+            TokenKind::Ident => Some(self.consume_any()),
+            _ => None,
+        };
+
+        // reference to the enum declared before, or a forward declaration
+        if !self.is("{") {
+            let tag = match tag {
+                Some(tag) => tag,
+                None => {
+                    return Err(
+                        self.error_at("expected-identifier", "expected an identifier or '{'")
+                    )
+                }
+            };
+            match self.find_tag(&tag.string).cloned() {
+                Some(prev) => self.check_tag_kind(&prev, &tag, "enum")?,
+                None => {
+                    let scope = self.tags.last_mut().unwrap();
+                    scope.insert(tag.string, Tag::Enum(tag.span, false));
+                }
+            }
+            return Ok(Type::Int);
+        }
+
+        if let Some(tag) = &tag {
+            if let Some(prev) = self.tags.last().unwrap().get(&tag.string).cloned() {
+                self.check_tag_kind(&prev, tag, "enum")?;
+                if let Tag::Enum(prev_span, true) = prev {
+                    let msg = format!("redefinition of '{}'", tag.string);
+                    let diag = Diagnostic::new(
+                        DiagnosticKind::Semantic,
+                        "redefinition",
+                        &msg,
+                        Some(tag.span),
+                    );
+                    return Err(diag.with_note("previous definition is here", prev_span));
+                }
+            }
+            let scope = self.tags.last_mut().unwrap();
+            scope.insert(tag.string.clone(), Tag::Enum(tag.span, true));
+        }
+        let open = self.cur_token().span;
+        self.consume("{");
+
+        // each enumerator without a value is the previous one plus 1
+        let mut val = 0;
+        loop {
+            if self.cur_token().kind != TokenKind::Ident {
+                return Err(self.error_at("expected-identifier", "expected an identifier"));
+            }
+            let ident = self.consume_any();
+            if self.consume("=") {
+                let expr = self.constant_expr()?;
+                val = match sema::eval(&expr) {
+                    Some(val) => val,
+                    None => {
+                        let msg = "enumerator value is not a constant";
+                        return Err(Diagnostic::new(
+                            DiagnosticKind::Semantic,
+                            "non-constant-enumerator",
+                            msg,
+                            Some(expr.span()),
+                        ));
+                    }
+                };
+            }
+            if i32::try_from(val).is_err() {
+                let msg = "ISO C restricts enumerator values to range of 'int'";
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "enumerator-too-large",
+                    msg,
+                    Some(self.span_from(ident.span)),
+                ));
+            }
+            self.declare_enumerator(&ident, val)?;
+            val += 1;
+
+            if !self.consume(",") || self.is("}") {
+                break;
+            }
+        }
+        self.expected_closing("}", open)?;
+        Ok(Type::Int)
+    }
+
+    // enumerators share the names with the variables in the scope
+    fn declare_enumerator(&mut self, ident: &Token, val: i64) -> Result<(), Diagnostic> {
+        let scope = self.scopes.last_mut().unwrap();
+        let (msg, prev) = match scope.get(&ident.string) {
+            Some(VarScope::Enum(_, prev)) => (
+                format!("redefinition of enumerator '{}'", ident.string),
+                *prev,
+            ),
+            Some(prev) => (
+                format!(
+                    "redefinition of '{}' as different kind of symbol",
+                    ident.string
+                ),
+                prev.span(),
+            ),
+            None => {
+                scope.insert(ident.string.clone(), VarScope::Enum(val, ident.span));
+                return Ok(());
+            }
+        };
+        let diag = Diagnostic::new(
+            DiagnosticKind::Semantic,
+            "enumerator-too-large",
+            &msg,
+            Some(ident.span),
+        );
+        Err(diag.with_note("previous definition is here", prev))
+    }
+
+    // struct_decl = ("struct" | "union") ident? ("{" struct_member* "}")?
+    // struct_member = declspec (declarator ("," declarator)*)? ";"
+    fn struct_decl(&mut self) -> Result<Type, Diagnostic> {
+        let keyword = self.consume_any();
+        let is_union = keyword.string == "union";
+        let tag = match self.cur_token().kind {
+            TokenKind::Ident => Some(self.consume_any()),
+            _ => None,
+        };
+        let open = self.cur_token().span;
+
+        // reference to the struct declared before, or a declaration without the members
+        if !self.is("{") {
+            let tag = match tag {
+                Some(tag) => tag,
+                None => {
+                    return Err(
+                        self.error_at("expected-identifier", "expected an identifier or '{'")
+                    )
+                }
+            };
+            if let Some(prev) = self.find_tag(&tag.string).cloned() {
+                self.check_tag_kind(&prev, &tag, &keyword.string)?;
+                if let Tag::Struct(s) = prev {
+                    return Ok(Type::Struct(s));
+                }
+            }
+            let s = StructRef::new(Some(tag.string.clone()), is_union, tag.span);
+            let scope = self.tags.last_mut().unwrap();
+            scope.insert(tag.string, Tag::Struct(s.clone()));
+            return Ok(Type::Struct(s));
+        }
 
-        return AST::Node {
-            kind: NodeKind::FuncCall {
-                name: func_name,
-                argv: Box::new(argv),
+        // definition, which completes the struct declared in the same scope
+        // the tag is visible in the members (ex: struct node { struct node *next; })
+        let s = match &tag {
+            Some(tag) => match self.tags.last().unwrap().get(&tag.string).cloned() {
+                Some(prev) => {
+                    self.check_tag_kind(&prev, tag, &keyword.string)?;
+                    let s = match prev {
+                        Tag::Struct(s) => s,
+                        Tag::Enum(..) => unreachable!(),
+                    };
+                    if s.is_complete() {
+                        let msg = format!("redefinition of '{}'", tag.string);
+                        let diag = Diagnostic::new(
+                            DiagnosticKind::Semantic,
+                            "redefinition",
+                            &msg,
+                            Some(tag.span),
+                        );
+                        return Err(diag.with_note("previous definition is here", s.span()));
+                    }
+                    s.0.borrow_mut().span = tag.span;
+                    s
+                }
+                None => {
+                    let s = StructRef::new(Some(tag.string.clone()), is_union, tag.span);
+                    let scope = self.tags.last_mut().unwrap();
+                    scope.insert(tag.string.clone(), Tag::Struct(s.clone()));
+                    s
+                }
             },
+            None => StructRef::new(None, is_union, keyword.span),
         };
+        self.consume("{");
+
+        let mut members = Vec::new();
+        while !self.is("}") {
+            if self.is_eof() {
+                return Err(self.expected_closing("}", open).unwrap_err());
+            }
+            let declspec = self.declspec()?;
+            // the members of an anonymous struct belong to the enclosing one
+            if self.is(";") {
+                let start = self.cur_token().span;
+                self.consume(";");
+                match &declspec {
+                    Type::Struct(inner) if inner.0.borrow().tag.is_none() => {
+                        members.push((None, declspec));
+                    }
+                    _ => self.warnings.push(Diagnostic::warning(
+                        DiagnosticKind::Semantic,
+                        "missing-declaration",
+                        "declaration does not declare anything",
+                        start,
+                    )),
+                }
+                continue;
+            }
+            while {
+                let (ident, ty) = self.declarator(declspec.clone())?;
+                if ty.is_incomplete() {
+                    let msg = format!("field has incomplete type '{}'", ty);
+                    return Err(Diagnostic::new(
+                        DiagnosticKind::Semantic,
+                        "incomplete-field",
+                        &msg,
+                        Some(ident.span),
+                    ));
+                }
+                members.push((Some(ident), ty));
+                self.consume(",")
+            } {}
+            self.expected(";")?;
+        }
+        self.consume("}");
+        self.define_struct(&s, members)?;
+        Ok(Type::Struct(s))
     }
 
-    // declspec = "int"
-    fn declspec(&mut self) -> Type {
-        if self.consume("int") {
-            return Type::Int;
-        } else if self.consume("void") {
-            return Type::Void;
-        } else {
-            self.error_at("unexpected type");
-            Type::Int
+    // struct, union and enum can't be used for the same tag
+    fn check_tag_kind(&self, prev: &Tag, tag: &Token, keyword: &str) -> Result<(), Diagnostic> {
+        if prev.keyword() == keyword {
+            return Ok(());
+        }
+        let msg = format!(
+            "use of '{}' with tag type that does not match previous declaration",
+            tag.string
+        );
+        let diag = Diagnostic::new(
+            DiagnosticKind::Semantic,
+            "mismatched-tag",
+            &msg,
+            Some(tag.span),
+        );
+        Err(diag.with_note("previous use is here", prev.span()))
+    }
+
+    // lay out the members with their alignment
+    // the members of a struct follow one another, and those of a union overlap
+    fn define_struct(
+        &self,
+        s: &StructRef,
+        members: Vec<(Option<Token>, Type)>,
+    ) -> Result<(), Diagnostic> {
+        let is_union = s.is_union();
+        let mut list: Vec<Member> = Vec::new();
+        let mut offset = 0;
+        let mut align = 1;
+        for (ident, ty) in members {
+            let start = if is_union {
+                0
+            } else {
+                align_to(offset, ty.align())
+            };
+            offset = if is_union {
+                offset.max(ty.size())
+            } else {
+                start + ty.size()
+            };
+            align = align.max(ty.align());
+
+            let flattened = match (&ident, &ty) {
+                (Some(ident), _) => vec![(ident.string.clone(), ty.clone(), start, ident.span)],
+                (None, Type::Struct(inner)) => inner
+                    .members()
+                    .into_iter()
+                    .map(|m| (m.name, m.ty, start + m.offset, inner.span()))
+                    .collect(),
+                (None, _) => Vec::new(),
+            };
+            for (name, ty, offset, span) in flattened {
+                if list.iter().any(|m| m.name == name) {
+                    let msg = format!("duplicate member '{}'", name);
+                    return Err(Diagnostic::new(
+                        DiagnosticKind::Semantic,
+                        "duplicate-member",
+                        &msg,
+                        Some(span),
+                    ));
+                }
+                list.push(Member { name, ty, offset });
+            }
+        }
+        let mut def = s.0.borrow_mut();
+        def.members = list;
+        def.size = align_to(offset, align);
+        def.align = align;
+        def.is_complete = true;
+        Ok(())
+    }
+
+    // declarator of a variable, which must have the name
+    fn declarator(&mut self, ty: Type) -> Result<(Token, Type), Diagnostic> {
+        let (ident, ty) = self.named_declarator(ty)?;
+        if ty == Type::Void {
+            let msg = "Type void must not be a variable type";
+            return Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "void-variable",
+                msg,
+                Some(ident.span),
+            ));
+        }
+        Ok((ident, ty))
+    }
+
+    fn named_declarator(&mut self, ty: Type) -> Result<(Token, Type), Diagnostic> {
+        match self.declarator_opt(ty)? {
+            (Some(ident), ty) => Ok((ident, ty)),
+            (None, _) => Err(self.error_at("expected-identifier", "expected an identifier")),
         }
     }
 
-    // declarator = "*"* ident<Token>
-    // Todo 現在はint型にしか対応していない
-    fn declarator(&mut self, mut ty: Type) -> (String, Type) {
+    // declarator = "*"* ("(" declarator ")" | ident<Token>?) type_suffix
+    // the name is omitted in the parameters (ex: int (*)(int))
+    fn declarator_opt(&mut self, mut ty: Type) -> Result<(Option<Token>, Type), Diagnostic> {
         while self.consume("*") {
-            //panic!("pointer type is not implemented");
             ty = Type::Ptr(Box::new(ty));
         }
-        if ty == Type::Void {
-            self.error_at("Type void must not be a variable type");
+        // the suffix after the parentheses applies first
+        // int (*fp)(int) is a pointer to int (int)
+        if self.is("(") && self.is_nested_declarator() {
+            let open = self.cur_token().span;
+            let start = self.pos;
+            self.skip_parens()?;
+            let ty = self.type_suffix(ty)?;
+            let end = self.pos;
+            self.pos = start + 1;
+            let ret = self.declarator_opt(ty)?;
+            self.expected_closing(")", open)?;
+            self.pos = end;
+            return Ok(ret);
+        }
+        let ident = match self.cur_token().kind {
+            TokenKind::Ident => Some(self.consume_any()),
+            _ => None,
+        };
+        Ok((ident, self.type_suffix(ty)?))
+    }
+
+    // whether "(" begins a declarator rather than the parameters
+    fn is_nested_declarator(&self) -> bool {
+        match self.tokens.get(self.pos + 1) {
+            Some(next) if next.string == "*" || next.string == "(" => true,
+            Some(next) => next.kind == TokenKind::Ident && !self.is_typename(next),
+            None => false,
+        }
+    }
+
+    // skip the tokens to the matching ")"
+    fn skip_parens(&mut self) -> Result<(), Diagnostic> {
+        let open = self.cur_token().span;
+        let mut depth = 0;
+        loop {
+            if self.is_eof() {
+                return self.expected_closing(")", open);
+            }
+            if self.is("(") {
+                depth += 1;
+            } else if self.is(")") {
+                depth -= 1;
+            }
+            self.consume_any();
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // type_name = declspec declarator
+    // type without the name (ex: int *, char [4], int (*)(int))
+    fn type_name(&mut self) -> Result<Type, Diagnostic> {
+        let declspec = self.declspec()?;
+        match self.declarator_opt(declspec)? {
+            (None, ty) => Ok(ty),
+            (Some(ident), _) => Err(Diagnostic::new(
+                DiagnosticKind::Syntax,
+                "expected-token",
+                "expected ')'",
+                Some(ident.span),
+            )),
+        }
+    }
+
+    // type_suffix = "(" func_params ")" | ("[" const_expr? "]")*
+    // int m[3][4] is an array of 3 arrays of 4 ints
+    // the size is omitted only in the parameters (ex: int a[])
+    fn type_suffix(&mut self, ty: Type) -> Result<Type, Diagnostic> {
+        let open = self.cur_token().span;
+        if self.consume("(") {
+            let (_, ty) = self.func_params(ty)?;
+            self.expected_closing(")", open)?;
+            return Ok(ty);
+        }
+        if !self.consume("[") {
+            return Ok(ty);
         }
+        if self.consume("]") {
+            let elem = self.type_suffix(ty)?;
+            return Ok(Type::Array(Box::new(elem), 0));
+        }
+        let expr = self.constant_expr()?;
+        let len = match sema::eval(&expr) {
+            Some(len) if len < 0 => {
+                let msg = "array has negative size";
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "negative-array-size",
+                    msg,
+                    Some(expr.span()),
+                ));
+            }
+            Some(len) => len as usize,
+            None => {
+                let msg = "array size is not a constant";
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Semantic,
+                    "non-constant-array-size",
+                    msg,
+                    Some(expr.span()),
+                ));
+            }
+        };
+        self.expected_closing("]", open)?;
+        let elem = self.type_suffix(ty)?;
+        Ok(Type::Array(Box::new(elem), len))
+    }
 
-        let ident_name = self.consume_any().string;
-        (ident_name, ty)
+    // typedef = "typedef" declspec declarator ("," declarator)*
+    // the declarators declare the names of the types instead of the variables
+    fn typedef(&mut self) -> Result<(), Diagnostic> {
+        self.consume("typedef");
+        let declspec = self.declspec()?;
+        while {
+            let (ident, ty) = self.named_declarator(declspec.clone())?;
+            self.declare_typedef(&ident, ty)?;
+            self.consume(",")
+        } {}
+        Ok(())
     }
 
-    // declaration = declspec (declarator ("=" expr)? ("," declarator ("=" expr)?)*)? ";"
-    fn declaration(&mut self) -> AST {
+    // declaration = "extern"? declspec (declarator ("=" expr)? ("," declarator ("=" expr)?)*)? ";"
+    //             | typedef ";"
+    fn declaration(&mut self) -> Result<AST, Diagnostic> {
         let mut inits: Vec<AST> = Vec::new();
-        let declspec = self.declspec();
+        let start = self.cur_token().span;
+        if self.is("typedef") {
+            self.typedef()?;
+            return Ok(new_node(NodeKind::Block(inits), self.span_from(start)));
+        }
+        let is_extern = self.consume("extern");
+        let declspec = self.declspec()?;
+        // only declares the struct tag
+        if self.is(";") {
+            return Ok(new_node(NodeKind::Block(inits), self.span_from(start)));
+        }
 
         while {
             // 変数名と型を取得
             // ここで型を取得するのは int a, *b;のような宣言がありえるため
-            let (var_name, ty) = self.declarator(declspec.clone());
-
-            let mut offset;
-            offset = match self.locals.get(&var_name) {
-                // variable names are already registered
-                Some((ofs, _)) => {
-                    //self.locals.insert(var_name.clone(), (offs, ));
-                    *ofs
-                }
-                // not registered
-                None => {
-                    self.locals
-                        .insert(var_name.clone(), (self.offset, ty.clone()));
-                    let var_size = match ty {
-                        Type::Int => 8,
-                        Type::Ptr(_) => 8,
-                        _ => 0, // voidのカバー
-                    };
-                    //println!("{:?} {:?} {:?}", var_name, ty, var_size);
-                    offset = self.offset;
-                    self.offset += var_size;
-                    offset
-                }
-            };
-
-            if self.consume("=") {
-                let init = AST::Node {
-                    kind: NodeKind::Assign(
-                        Box::new(AST::Node {
-                            kind: NodeKind::Var {
-                                name: var_name.clone(),
-                                offset: offset,
-                                ty: ty.clone(),
-                            },
-                        }),
-                        Box::new(self.expr()),
-                    ),
-                };
-                inits.push(AST::Node {
-                    kind: NodeKind::ExprStmt(Box::new(init)),
-                });
+            let (ident, ty) = self.declarator(declspec.clone())?;
+            // the functions are declared in the file scope (ex: int f(int);)
+            if let Type::Func { .. } = ty {
+                self.declare_func(&ident, ty, false)?;
+            } else if is_extern {
+                self.declare_extern(&ident, ty)?;
+            } else if self.consume("=") {
+                let offset = self.declare_local(&ident, &ty)?;
+                let var_name = ident.string;
+                let var = new_node(
+                    NodeKind::Var {
+                        name: var_name.clone(),
+                        offset,
+                        ty: ty.clone(),
+                    },
+                    ident.span,
+                );
+                let init = new_binary(NodeKind::Assign, var, self.assign()?);
+                let span = init.span();
+                inits.push(new_node(NodeKind::ExprStmt(Box::new(init)), span));
+            } else {
+                self.declare_local(&ident, &ty)?;
             }
 
             self.consume(",") // loop only while this is met
         } {}
 
-        AST::Node {
-            kind: NodeKind::Block(Box::new(inits)),
-        }
+        Ok(new_node(NodeKind::Block(inits), self.span_from(start)))
     }
 }
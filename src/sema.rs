@@ -0,0 +1,925 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::parse::{new_node, NodeKind, Type, AST};
+use crate::source::Span;
+
+#[test]
+fn test_analyze() {
+    use crate::parse::parse;
+    use crate::source::SourceMap;
+    use crate::tokenize::tokenize;
+    let mut map = SourceMap::new();
+    let src = "int main(void) { unsigned u; char c; return u + c; }";
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let asts = parse(tokens, &mut Vec::new()).unwrap();
+    let asts = analyze(asts, &mut Vec::new()).unwrap();
+    println!("{:?}", asts);
+    // char is converted to unsigned int, and the sum is converted to int
+    let stmts = match asts[0].kind() {
+        NodeKind::FuncDecl { stmts, .. } => stmts,
+        _ => panic!("expected a function"),
+    };
+    let ret = match stmts[2].kind() {
+        NodeKind::Return(expr) => *expr,
+        _ => panic!("expected a return statement"),
+    };
+    assert_eq!(ret.ty(), Type::Int);
+    let sum = match ret.kind() {
+        NodeKind::Cast(sum) => *sum,
+        _ => panic!("expected a conversion"),
+    };
+    assert_eq!(sum.ty(), Type::UInt);
+    match sum.kind() {
+        NodeKind::Plus(_, c) => assert!(matches!(c.kind(), NodeKind::Cast(_))),
+        _ => panic!("expected an addition"),
+    }
+}
+
+#[test]
+fn test_op_assign() {
+    use crate::parse::parse;
+    use crate::source::SourceMap;
+    use crate::tokenize::tokenize;
+    let mut map = SourceMap::new();
+    let src = "int f(void); int main(void) { int a[2]; a[1] += 1; a[f()] += 1; return 0; }";
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let asts = parse(tokens, &mut Vec::new()).unwrap();
+    let asts = analyze(asts, &mut Vec::new()).unwrap();
+    let stmts = match asts[0].kind() {
+        NodeKind::FuncDecl { stmts, .. } => stmts,
+        _ => panic!("expected a function"),
+    };
+    let expr = |stmt: &AST| match stmt.kind() {
+        NodeKind::ExprStmt(expr) => expr.kind(),
+        _ => panic!("expected an expression statement"),
+    };
+    // a[1] is evaluated twice, but a[f()] only once through the temporary pointer
+    assert!(matches!(expr(&stmts[1]), NodeKind::Assign(..)));
+    assert!(matches!(expr(&stmts[2]), NodeKind::Comma(..)));
+}
+
+// semantic analysis between the parser and the code generator
+// every expression gets its type, the implicit conversions are made explicit as Cast,
+// and the operands are checked against the operators
+struct Analyzer {
+    func: (String, Type), // name and return type of the function being analyzed
+    warnings: Vec<Diagnostic>,
+}
+
+// the warnings are appended to warnings even if the analysis fails
+pub fn analyze(asts: Vec<AST>, warnings: &mut Vec<Diagnostic>) -> Result<Vec<AST>, Diagnostic> {
+    let mut analyzer = Analyzer {
+        func: (String::new(), Type::Int),
+        warnings: Vec::new(),
+    };
+    let ret = asts.into_iter().map(|ast| analyzer.stmt(ast)).collect();
+    warnings.append(&mut analyzer.warnings);
+    ret
+}
+
+// analyze an expression out of the functions
+// the parser evaluates the constant expressions with it (ex: array lengths, enumerators)
+pub fn analyze_expr(ast: AST, warnings: &mut Vec<Diagnostic>) -> Result<AST, Diagnostic> {
+    let mut analyzer = Analyzer {
+        func: (String::new(), Type::Int),
+        warnings: Vec::new(),
+    };
+    let ret = analyzer.expr(ast);
+    warnings.append(&mut analyzer.warnings);
+    ret
+}
+
+// value of the analyzed integer constant expression (None if it is not a constant)
+// each operation is done in the type of its node like the generated code
+pub fn eval(ast: &AST) -> Option<i64> {
+    let ty = ast.ty();
+    let val = match ast.kind() {
+        NodeKind::Num(val, _) => val,
+        NodeKind::Cast(operand) => eval(&operand)?,
+        NodeKind::Plus(l, r) => eval(&l)?.wrapping_add(eval(&r)?),
+        NodeKind::Minus(l, r) => eval(&l)?.wrapping_sub(eval(&r)?),
+        NodeKind::Mul(l, r) => eval(&l)?.wrapping_mul(eval(&r)?),
+        NodeKind::Div(l, r) => match (eval(&l)?, eval(&r)?) {
+            (_, 0) => return None,
+            (l, r) if ty.is_unsigned() => ((l as u64) / (r as u64)) as i64,
+            (l, r) => l.wrapping_div(r),
+        },
+        NodeKind::Mod(l, r) => match (eval(&l)?, eval(&r)?) {
+            (_, 0) => return None,
+            (l, r) if ty.is_unsigned() => ((l as u64) % (r as u64)) as i64,
+            (l, r) => l.wrapping_rem(r),
+        },
+        NodeKind::BitAnd(l, r) => eval(&l)? & eval(&r)?,
+        NodeKind::BitOr(l, r) => eval(&l)? | eval(&r)?,
+        NodeKind::BitXor(l, r) => eval(&l)? ^ eval(&r)?,
+        NodeKind::BitNot(operand) => !eval(&operand)?,
+        // the count is masked to 6 bits like shl and sar
+        NodeKind::Shl(l, r) => eval(&l)?.wrapping_shl(eval(&r)? as u32),
+        NodeKind::Shr(l, r) if ty.is_unsigned() => {
+            (eval(&l)? as u64).wrapping_shr(eval(&r)? as u32) as i64
+        }
+        NodeKind::Shr(l, r) => eval(&l)?.wrapping_shr(eval(&r)? as u32),
+        // the operands have been converted to their common type
+        NodeKind::Eq(l, r) => (eval(&l)? == eval(&r)?) as i64,
+        NodeKind::Ne(l, r) => (eval(&l)? != eval(&r)?) as i64,
+        NodeKind::Lt(l, r) if l.ty().is_unsigned() => {
+            ((eval(&l)? as u64) < (eval(&r)? as u64)) as i64
+        }
+        NodeKind::Lt(l, r) => (eval(&l)? < eval(&r)?) as i64,
+        NodeKind::Le(l, r) if l.ty().is_unsigned() => {
+            ((eval(&l)? as u64) <= (eval(&r)? as u64)) as i64
+        }
+        NodeKind::Le(l, r) => (eval(&l)? <= eval(&r)?) as i64,
+        NodeKind::LogAnd(l, r) => (eval(&l)? != 0 && eval(&r)? != 0) as i64,
+        NodeKind::LogOr(l, r) => (eval(&l)? != 0 || eval(&r)? != 0) as i64,
+        NodeKind::Not(operand) => (eval(&operand)? == 0) as i64,
+        _ => return None,
+    };
+    Some(normalize(val, &ty))
+}
+
+// truncate the value to the size of the type and extend it to 64 bits again
+fn normalize(val: i64, ty: &Type) -> i64 {
+    match ty {
+        Type::Char => val as i8 as i64,
+        Type::Short => val as i16 as i64,
+        Type::Int => val as i32 as i64,
+        Type::UChar => val as u8 as i64,
+        Type::UShort => val as u16 as i64,
+        Type::UInt => val as u32 as i64,
+        _ => val,
+    }
+}
+
+// check that the value of the expression can be converted to the type
+// as if by assignment; context describes the conversion in the messages
+// (ex: "passing 'int' to parameter of type 'char *'")
+fn check_conversion(
+    ty: &Type,
+    ast: &AST,
+    context: &str,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(), Diagnostic> {
+    let span = ast.span();
+    match (ty, ast.ty().decay()) {
+        (Type::Ptr(to), Type::Ptr(from)) => {
+            // void * converts to and from any pointer
+            if **to != *from && **to != Type::Void && *from != Type::Void {
+                let msg = format!("incompatible pointer types {}", context);
+                warnings.push(Diagnostic::warning(
+                    DiagnosticKind::Semantic,
+                    "incompatible-pointer-types",
+                    &msg,
+                    span,
+                ));
+            }
+            Ok(())
+        }
+        // 0 is the null pointer constant
+        (Type::Ptr(_), from) if from.is_integer() && eval(ast) == Some(0) => Ok(()),
+        (Type::Ptr(_), from) if from.is_integer() => {
+            let msg = format!("incompatible integer to pointer conversion {}", context);
+            Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "int-conversion",
+                &msg,
+                Some(span),
+            ))
+        }
+        (to, Type::Ptr(_)) if to.is_integer() => {
+            let msg = format!("incompatible pointer to integer conversion {}", context);
+            Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "int-conversion",
+                &msg,
+                Some(span),
+            ))
+        }
+        // structs are converted only to the same struct
+        (Type::Struct(to), Type::Struct(from)) if *to == from => Ok(()),
+        (to, from)
+            if *to == Type::Void
+                || from == Type::Void
+                || matches!(to, Type::Struct(_))
+                || matches!(from, Type::Struct(_)) =>
+        {
+            let msg = format!("incompatible types {}", context);
+            Err(Diagnostic::new(
+                DiagnosticKind::Semantic,
+                "incompatible-types",
+                &msg,
+                Some(span),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn typed(kind: NodeKind, span: Span, ty: Type) -> AST {
+    AST::Node {
+        kind,
+        span,
+        ty: Some(ty),
+    }
+}
+
+// convert the integer value to the type
+fn cast(ast: AST, ty: &Type) -> AST {
+    if let AST::Nil = ast {
+        return ast;
+    }
+    if ast.ty() == *ty || !ty.is_integer() || !ast.ty().is_integer() {
+        return ast;
+    }
+    let span = ast.span();
+    typed(NodeKind::Cast(Box::new(ast)), span, ty.clone())
+}
+
+// only the variables, *p (and a[i]) and the members of them designate the objects
+fn is_lvalue(ast: &AST) -> bool {
+    match ast.kind() {
+        NodeKind::Var { .. } | NodeKind::GVar { .. } | NodeKind::Deref(_) | NodeKind::Index(..) => {
+            true
+        }
+        NodeKind::Member { base, .. } => is_lvalue(&base),
+        _ => false,
+    }
+}
+
+// usual arithmetic conversions
+// the type which both integer operands of a binary operator are converted to
+fn common_type(l: &Type, r: &Type) -> Type {
+    let (l, r) = (l.promoted(), r.promoted());
+    let either = |ty: Type| l == ty || r == ty;
+    // long can represent every unsigned int
+    if either(Type::ULong) {
+        Type::ULong
+    } else if either(Type::Long) {
+        Type::Long
+    } else if either(Type::UInt) {
+        Type::UInt
+    } else {
+        Type::Int
+    }
+}
+
+// integers and pointers (including arrays and functions converted to them)
+fn is_scalar(ty: &Type) -> bool {
+    ty.is_integer() || matches!(ty.clone().decay(), Type::Ptr(_))
+}
+
+fn error(code: &'static str, msg: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(DiagnosticKind::Semantic, code, msg, Some(span))
+}
+
+// bug of ccr, not of the source (ex: a node the parser doesn't make)
+fn internal_error(msg: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(DiagnosticKind::Internal, "internal-error", msg, Some(span))
+}
+
+// the pointer arithmetic needs the size of the pointee
+fn check_pointee(base: &Type, span: Span) -> Result<(), Diagnostic> {
+    if !base.is_incomplete() {
+        return Ok(());
+    }
+    let msg = format!("arithmetic on a pointer to an incomplete type '{}'", base);
+    Err(error("incomplete-pointee", &msg, span))
+}
+
+fn invalid_operands(l: &AST, r: &AST, span: Span) -> Diagnostic {
+    let msg = format!(
+        "invalid operands to binary expression ('{}' and '{}')",
+        l.ty(),
+        r.ty()
+    );
+    error("invalid-operands", &msg, span)
+}
+
+impl Analyzer {
+    fn stmt(&mut self, ast: AST) -> Result<AST, Diagnostic> {
+        if let AST::Nil = ast {
+            return Ok(ast);
+        }
+        let span = ast.span();
+        let kind = match ast.kind() {
+            NodeKind::FuncDecl {
+                name,
+                args,
+                ret_type,
+                frame_size,
+                stmts,
+            } => {
+                self.func = (name.clone(), ret_type.clone());
+                let stmts = stmts
+                    .into_iter()
+                    .map(|stmt| self.stmt(stmt))
+                    .collect::<Result<_, _>>()?;
+                NodeKind::FuncDecl {
+                    name,
+                    args,
+                    ret_type,
+                    frame_size,
+                    stmts,
+                }
+            }
+            NodeKind::GVarDecl { .. } => return Ok(ast),
+            NodeKind::ExprStmt(expr) => NodeKind::ExprStmt(Box::new(self.opt_expr(*expr)?)),
+            NodeKind::Block(stmts) => NodeKind::Block(
+                stmts
+                    .into_iter()
+                    .map(|stmt| self.stmt(stmt))
+                    .collect::<Result<_, _>>()?,
+            ),
+            // the value is converted to the return type
+            NodeKind::Return(expr) => {
+                let expr = self.opt_expr(*expr)?;
+                let (name, ret_ty) = self.func.clone();
+                match (&expr, &ret_ty) {
+                    (AST::Nil, Type::Void) => (),
+                    (AST::Nil, _) => {
+                        let msg = format!("non-void function '{}' should return a value", name);
+                        return Err(error("return-type", &msg, span));
+                    }
+                    (_, Type::Void) => {
+                        let msg = format!("void function '{}' should not return a value", name);
+                        return Err(error("return-type", &msg, expr.span()));
+                    }
+                    _ => {
+                        let context = format!(
+                            "returning '{}' from a function with result type '{}'",
+                            expr.ty(),
+                            ret_ty
+                        );
+                        check_conversion(&ret_ty, &expr, &context, &mut self.warnings)?;
+                    }
+                }
+                NodeKind::Return(Box::new(cast(expr, &ret_ty)))
+            }
+            NodeKind::If { cond, then, els } => NodeKind::If {
+                cond: Box::new(self.cond(*cond)?),
+                then: Box::new(self.stmt(*then)?),
+                els: Box::new(self.stmt(*els)?),
+            },
+            NodeKind::For { a, b, c, proc } => NodeKind::For {
+                a: Box::new(self.stmt(*a)?),
+                b: Box::new(self.cond(*b)?),
+                c: Box::new(self.opt_expr(*c)?),
+                proc: Box::new(self.stmt(*proc)?),
+            },
+            _ => return Err(internal_error("unexpected node in a statement", span)),
+        };
+        Ok(new_node(kind, span))
+    }
+
+    // expression which can be omitted (Nil)
+    fn opt_expr(&mut self, ast: AST) -> Result<AST, Diagnostic> {
+        match ast {
+            AST::Nil => Ok(ast),
+            _ => self.expr(ast),
+        }
+    }
+
+    // controlling expression of if and for, which must be a scalar
+    fn cond(&mut self, ast: AST) -> Result<AST, Diagnostic> {
+        let ast = self.opt_expr(ast)?;
+        if let AST::Node { .. } = ast {
+            let ty = ast.ty();
+            if let Type::Void | Type::Struct(_) = ty {
+                let msg = format!(
+                    "statement requires expression of scalar type ('{}' invalid)",
+                    ty
+                );
+                return Err(error("non-scalar-condition", &msg, ast.span()));
+            }
+        }
+        Ok(ast)
+    }
+
+    fn expr(&mut self, ast: AST) -> Result<AST, Diagnostic> {
+        let span = ast.span();
+        match ast.kind() {
+            NodeKind::Num(_, ty) | NodeKind::Var { ty, .. } | NodeKind::GVar { ty, .. } => {
+                Ok(typed(ast.kind(), span, ty))
+            }
+            // the terminating NUL is a part of the array
+            NodeKind::Str(s) => {
+                let ty = Type::Array(Box::new(Type::Char), s.len() + 1);
+                Ok(typed(ast.kind(), span, ty))
+            }
+            NodeKind::FuncCall {
+                name,
+                argv,
+                ty,
+                decl,
+            } => {
+                let argv = self.args(argv)?;
+                let note = format!("'{}' declared here", name);
+                self.check_args(&ty, Some((&note, decl)), &argv, span)?;
+                let ret = match &ty {
+                    Type::Func { ret, .. } => *ret.clone(),
+                    _ => return Err(internal_error("call of a non-function", span)),
+                };
+                let kind = NodeKind::FuncCall {
+                    name,
+                    argv,
+                    ty,
+                    decl,
+                };
+                Ok(typed(kind, span, ret))
+            }
+            NodeKind::FuncPtrCall { callee, argv } => {
+                let callee = self.expr(*callee)?;
+                // the callee is a function, or a pointer to it
+                let ty = match callee.ty().decay() {
+                    Type::Ptr(base) => *base,
+                    ty => ty,
+                };
+                let ret = match &ty {
+                    Type::Func { ret, .. } => *ret.clone(),
+                    _ => {
+                        let msg = format!(
+                            "called object type '{}' is not a function or function pointer",
+                            callee.ty().decay()
+                        );
+                        return Err(error("not-a-function", &msg, callee.span()));
+                    }
+                };
+                let argv = self.args(argv)?;
+                self.check_args(&ty, None, &argv, span)?;
+                let kind = NodeKind::FuncPtrCall {
+                    callee: Box::new(callee),
+                    argv,
+                };
+                Ok(typed(kind, span, ret))
+            }
+            NodeKind::Assign(l, r) => {
+                let l = self.expr(*l)?;
+                let r = self.expr(*r)?;
+                self.assign(l, r, span)
+            }
+            NodeKind::OpAssign(op, tmp) => self.op_assign(*op, tmp, span),
+            NodeKind::PostInc(update) => self.post_incdec(*update, true, span),
+            NodeKind::PostDec(update) => self.post_incdec(*update, false, span),
+            // the parser has looked up the member
+            NodeKind::Member {
+                base,
+                name,
+                offset,
+                ty,
+            } => {
+                let base = Box::new(self.expr(*base)?);
+                let kind = NodeKind::Member {
+                    base,
+                    name,
+                    offset,
+                    ty: ty.clone(),
+                };
+                Ok(typed(kind, span, ty))
+            }
+            NodeKind::Deref(operand) => {
+                let operand = self.expr(*operand)?;
+                self.deref(operand, span)
+            }
+            // a[i] is *(a + i), and i[a] is also allowed since the addition is commutative
+            NodeKind::Index(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                match (l.ty().decay(), r.ty().decay()) {
+                    (Type::Ptr(_), ty) | (ty, Type::Ptr(_)) if ty.is_integer() => (),
+                    (Type::Ptr(_), _) | (_, Type::Ptr(_)) => {
+                        return Err(error(
+                            "non-integer-subscript",
+                            "array subscript is not an integer",
+                            r.span(),
+                        ));
+                    }
+                    _ => {
+                        let msg = "subscripted value is not an array or pointer";
+                        return Err(error("invalid-subscript", msg, l.span()));
+                    }
+                }
+                let addr = self.add(l, r, span)?;
+                self.deref(addr, span)
+            }
+            NodeKind::Addr(operand) => {
+                let operand = self.expr(*operand)?;
+                let ty = operand.ty();
+                if !is_lvalue(&operand) {
+                    let msg = format!("cannot take the address of an rvalue of type '{}'", ty);
+                    return Err(error("address-of-rvalue", &msg, span));
+                }
+                let ty = Type::Ptr(Box::new(ty));
+                Ok(typed(NodeKind::Addr(Box::new(operand)), span, ty))
+            }
+            NodeKind::Plus(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.add(l, r, span)
+            }
+            NodeKind::Minus(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.sub(l, r, span)
+            }
+            NodeKind::Mul(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::Mul, l, r, span)
+            }
+            NodeKind::Div(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::Div, l, r, span)
+            }
+            NodeKind::Mod(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::Mod, l, r, span)
+            }
+            NodeKind::BitAnd(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::BitAnd, l, r, span)
+            }
+            NodeKind::BitOr(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::BitOr, l, r, span)
+            }
+            NodeKind::BitXor(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.arithmetic(NodeKind::BitXor, l, r, span)
+            }
+            NodeKind::BitNot(operand) => {
+                let operand = self.expr(*operand)?;
+                let ty = operand.ty();
+                if !ty.is_integer() {
+                    let msg = format!("invalid argument type '{}' to unary expression", ty);
+                    return Err(error("invalid-unary-operand", &msg, span));
+                }
+                let ty = ty.promoted();
+                let operand = cast(operand, &ty);
+                Ok(typed(NodeKind::BitNot(Box::new(operand)), span, ty))
+            }
+            NodeKind::Shl(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.shift(NodeKind::Shl, l, r, span)
+            }
+            NodeKind::Shr(l, r) => {
+                let (l, r) = (self.expr(*l)?, self.expr(*r)?);
+                self.shift(NodeKind::Shr, l, r, span)
+            }
+            NodeKind::Eq(l, r) => self.comparison(NodeKind::Eq, *l, *r, span),
+            NodeKind::Ne(l, r) => self.comparison(NodeKind::Ne, *l, *r, span),
+            NodeKind::Lt(l, r) => self.comparison(NodeKind::Lt, *l, *r, span),
+            NodeKind::Le(l, r) => self.comparison(NodeKind::Le, *l, *r, span),
+            NodeKind::LogAnd(l, r) => self.logical(NodeKind::LogAnd, *l, *r, span),
+            NodeKind::LogOr(l, r) => self.logical(NodeKind::LogOr, *l, *r, span),
+            NodeKind::Not(operand) => {
+                let operand = self.expr(*operand)?;
+                if !is_scalar(&operand.ty()) {
+                    let msg = format!(
+                        "invalid argument type '{}' to unary expression",
+                        operand.ty()
+                    );
+                    return Err(error("invalid-unary-operand", &msg, span));
+                }
+                Ok(typed(NodeKind::Not(Box::new(operand)), span, Type::Int))
+            }
+            NodeKind::Comma(l, r) => {
+                let l = self.expr(*l)?;
+                let r = self.expr(*r)?;
+                let ty = r.ty();
+                Ok(typed(NodeKind::Comma(Box::new(l), Box::new(r)), span, ty))
+            }
+            _ => Err(internal_error("unexpected node in an expression", span)),
+        }
+    }
+
+    // l = r where r is converted to the type of l
+    fn assign(&mut self, l: AST, r: AST, span: Span) -> Result<AST, Diagnostic> {
+        let ty = l.ty();
+        if let Type::Array(..) = ty {
+            let msg = format!("array type '{}' is not assignable", ty);
+            return Err(error("not-assignable", &msg, l.span()));
+        }
+        if let Type::Func { .. } = ty {
+            let msg = format!("non-object type '{}' is not assignable", ty);
+            return Err(error("not-assignable", &msg, l.span()));
+        }
+        if !is_lvalue(&l) {
+            return Err(error(
+                "not-assignable",
+                "expression is not assignable",
+                l.span(),
+            ));
+        }
+        let context = format!("assigning to '{}' from '{}'", ty, r.ty());
+        check_conversion(&ty, &r, &context, &mut self.warnings)?;
+        let r = cast(r, &ty);
+        Ok(typed(NodeKind::Assign(Box::new(l), Box::new(r)), span, ty))
+    }
+
+    // A op= B is A = A op B
+    // tmp is the offset of the pointer to A if A has side effects: (tmp = &A, *tmp = *tmp op B)
+    fn op_assign(&mut self, op: AST, tmp: Option<usize>, span: Span) -> Result<AST, Diagnostic> {
+        let op_span = op.span();
+        let (l, r) = match op.kind() {
+            NodeKind::Plus(l, r)
+            | NodeKind::Minus(l, r)
+            | NodeKind::Mul(l, r)
+            | NodeKind::Div(l, r)
+            | NodeKind::Mod(l, r)
+            | NodeKind::BitAnd(l, r)
+            | NodeKind::BitOr(l, r)
+            | NodeKind::BitXor(l, r)
+            | NodeKind::Shl(l, r)
+            | NodeKind::Shr(l, r) => (self.expr(*l)?, self.expr(*r)?),
+            _ => {
+                return Err(internal_error(
+                    "unexpected node in a compound assignment",
+                    span,
+                ))
+            }
+        };
+        if !is_lvalue(&l) {
+            return Err(error(
+                "not-assignable",
+                "expression is not assignable",
+                l.span(),
+            ));
+        }
+        let (addr, target) = match tmp {
+            None => (None, l),
+            Some(offset) => {
+                let l_span = l.span();
+                let ty = Type::Ptr(Box::new(l.ty()));
+                let var = NodeKind::Var {
+                    name: String::new(),
+                    offset,
+                    ty: ty.clone(),
+                };
+                let tmp = typed(var, l_span, ty.clone());
+                let addr = typed(NodeKind::Addr(Box::new(l)), l_span, ty.clone());
+                let kind = NodeKind::Assign(Box::new(tmp.clone()), Box::new(addr));
+                (Some(typed(kind, l_span, ty)), self.deref(tmp, l_span)?)
+            }
+        };
+        let l = target.clone();
+        let value = match op.kind() {
+            NodeKind::Plus(..) => self.add(l, r, op_span)?,
+            NodeKind::Minus(..) => self.sub(l, r, op_span)?,
+            NodeKind::Mul(..) => self.arithmetic(NodeKind::Mul, l, r, op_span)?,
+            NodeKind::Div(..) => self.arithmetic(NodeKind::Div, l, r, op_span)?,
+            NodeKind::Mod(..) => self.arithmetic(NodeKind::Mod, l, r, op_span)?,
+            NodeKind::BitAnd(..) => self.arithmetic(NodeKind::BitAnd, l, r, op_span)?,
+            NodeKind::BitOr(..) => self.arithmetic(NodeKind::BitOr, l, r, op_span)?,
+            NodeKind::BitXor(..) => self.arithmetic(NodeKind::BitXor, l, r, op_span)?,
+            NodeKind::Shl(..) => self.shift(NodeKind::Shl, l, r, op_span)?,
+            _ => self.shift(NodeKind::Shr, l, r, op_span)?,
+        };
+        let assign = self.assign(target, value, span)?;
+        match addr {
+            None => Ok(assign),
+            Some(addr) => {
+                let ty = assign.ty();
+                let kind = NodeKind::Comma(Box::new(addr), Box::new(assign));
+                Ok(typed(kind, span, ty))
+            }
+        }
+    }
+
+    // A++ is (A += 1) - 1 converted back to the type of A (A-- likewise)
+    fn post_incdec(&mut self, update: AST, is_inc: bool, span: Span) -> Result<AST, Diagnostic> {
+        let update = self.expr(update)?;
+        let ty = update.ty();
+        let one = typed(NodeKind::Num(1, Type::Int), span, Type::Int);
+        let value = if is_inc {
+            self.sub(update, one, span)?
+        } else {
+            self.add(update, one, span)?
+        };
+        Ok(cast(value, &ty))
+    }
+
+    // the arguments are passed in the registers, so the structs can't be passed
+    fn args(&mut self, argv: Vec<AST>) -> Result<Vec<AST>, Diagnostic> {
+        let argv: Vec<AST> = argv
+            .into_iter()
+            .map(|arg| self.expr(arg))
+            .collect::<Result<_, _>>()?;
+        if let Some(arg) = argv.iter().find(|arg| matches!(arg.ty(), Type::Struct(_))) {
+            let msg = "passing a struct by value is not supported";
+            return Err(error("unsupported-struct-argument", msg, arg.span()));
+        }
+        Ok(argv)
+    }
+
+    // both operands are converted to their common type
+    fn arithmetic(
+        &mut self,
+        kind: fn(Box<AST>, Box<AST>) -> NodeKind,
+        l: AST,
+        r: AST,
+        span: Span,
+    ) -> Result<AST, Diagnostic> {
+        if !l.ty().is_integer() || !r.ty().is_integer() {
+            return Err(invalid_operands(&l, &r, span));
+        }
+        let ty = common_type(&l.ty(), &r.ty());
+        let (l, r) = (cast(l, &ty), cast(r, &ty));
+        Ok(typed(kind(Box::new(l), Box::new(r)), span, ty))
+    }
+
+    // the operands are promoted separately, and the result has the type of the left one
+    fn shift(
+        &mut self,
+        kind: fn(Box<AST>, Box<AST>) -> NodeKind,
+        l: AST,
+        r: AST,
+        span: Span,
+    ) -> Result<AST, Diagnostic> {
+        if !l.ty().is_integer() || !r.ty().is_integer() {
+            return Err(invalid_operands(&l, &r, span));
+        }
+        let (ty, rty) = (l.ty().promoted(), r.ty().promoted());
+        let (l, r) = (cast(l, &ty), cast(r, &rty));
+        Ok(typed(kind(Box::new(l), Box::new(r)), span, ty))
+    }
+
+    // lhs + rhs where the integer added to a pointer is scaled by the size of the pointee
+    fn add(&mut self, l: AST, r: AST, span: Span) -> Result<AST, Diagnostic> {
+        match (l.ty().decay(), r.ty().decay()) {
+            (ty @ Type::Ptr(_), int) if int.is_integer() => {
+                let r = self.scaled(r, &ty, span)?;
+                Ok(typed(NodeKind::Plus(Box::new(l), Box::new(r)), span, ty))
+            }
+            // int + ptr is ptr + int
+            (int, ty @ Type::Ptr(_)) if int.is_integer() => {
+                let l = self.scaled(l, &ty, span)?;
+                Ok(typed(NodeKind::Plus(Box::new(r), Box::new(l)), span, ty))
+            }
+            (Type::Ptr(_), _) | (_, Type::Ptr(_)) => Err(invalid_operands(&l, &r, span)),
+            _ => self.arithmetic(NodeKind::Plus, l, r, span),
+        }
+    }
+
+    // lhs - rhs where ptr - int is scaled like add,
+    // and ptr - ptr is the number of the elements between them (long)
+    fn sub(&mut self, l: AST, r: AST, span: Span) -> Result<AST, Diagnostic> {
+        match (l.ty().decay(), r.ty().decay()) {
+            (Type::Ptr(lbase), Type::Ptr(rbase)) => {
+                if lbase != rbase {
+                    let msg = format!(
+                        "'{}' and '{}' are not pointers to compatible types",
+                        l.ty(),
+                        r.ty()
+                    );
+                    return Err(error("incompatible-pointer-subtraction", &msg, span));
+                }
+                check_pointee(&lbase, span)?;
+                let diff = typed(NodeKind::Minus(Box::new(l), Box::new(r)), span, Type::Long);
+                let size = typed(
+                    NodeKind::Num(lbase.size() as i64, Type::Long),
+                    span,
+                    Type::Long,
+                );
+                let kind = NodeKind::Div(Box::new(diff), Box::new(size));
+                Ok(typed(kind, span, Type::Long))
+            }
+            (ty @ Type::Ptr(_), int) if int.is_integer() => {
+                let r = self.scaled(r, &ty, span)?;
+                Ok(typed(NodeKind::Minus(Box::new(l), Box::new(r)), span, ty))
+            }
+            (Type::Ptr(_), _) | (_, Type::Ptr(_)) => Err(invalid_operands(&l, &r, span)),
+            _ => self.arithmetic(NodeKind::Minus, l, r, span),
+        }
+    }
+
+    // index multiplied by the size of the element the pointer points to
+    // span is the location of the whole arithmetic
+    fn scaled(&mut self, index: AST, ptr: &Type, span: Span) -> Result<AST, Diagnostic> {
+        let elem = match ptr {
+            Type::Ptr(elem) => {
+                check_pointee(elem, span)?;
+                elem.size()
+            }
+            _ => 1,
+        };
+        let span = index.span();
+        let size = typed(NodeKind::Num(elem as i64, Type::Int), span, Type::Int);
+        self.arithmetic(NodeKind::Mul, index, size, span)
+    }
+
+    // *operand, which must be a pointer (or an array)
+    fn deref(&mut self, operand: AST, span: Span) -> Result<AST, Diagnostic> {
+        match operand.ty().decay() {
+            Type::Ptr(base) => Ok(typed(NodeKind::Deref(Box::new(operand)), span, *base)),
+            ty => {
+                let msg = format!("indirection requires pointer operand ('{}' invalid)", ty);
+                Err(error("invalid-indirection", &msg, span))
+            }
+        }
+    }
+
+    // check the arguments against the prototype of the function
+    // decl is the note on the declaration of the function
+    fn check_args(
+        &mut self,
+        ty: &Type,
+        decl: Option<(&str, Span)>,
+        argv: &[AST],
+        call: Span,
+    ) -> Result<(), Diagnostic> {
+        let (params, is_variadic) = match ty {
+            Type::Func {
+                params: Some(params),
+                is_variadic,
+                ..
+            } => (params, *is_variadic),
+            _ => return Ok(()),
+        };
+        let with_note = |diag: Diagnostic| match decl {
+            Some((note, span)) => diag.with_note(note, span),
+            None => diag,
+        };
+        if argv.len() < params.len() {
+            let msg = format!(
+                "too few arguments to function call, expected {}, have {}",
+                params.len(),
+                argv.len()
+            );
+            return Err(with_note(error("too-few-arguments", &msg, call)));
+        }
+        if argv.len() > params.len() && !is_variadic {
+            let msg = format!(
+                "too many arguments to function call, expected {}, have {}",
+                params.len(),
+                argv.len()
+            );
+            let extra = argv[params.len()].span();
+            return Err(with_note(error("too-many-arguments", &msg, extra)));
+        }
+        for (arg, param) in argv.iter().zip(params) {
+            let context = format!("passing '{}' to parameter of type '{}'", arg.ty(), param);
+            check_conversion(param, arg, &context, &mut self.warnings)?;
+        }
+        Ok(())
+    }
+
+    // the operands are compared with 0, and the result is int (0 or 1)
+    fn logical(
+        &mut self,
+        kind: fn(Box<AST>, Box<AST>) -> NodeKind,
+        l: AST,
+        r: AST,
+        span: Span,
+    ) -> Result<AST, Diagnostic> {
+        let (l, r) = (self.expr(l)?, self.expr(r)?);
+        if !is_scalar(&l.ty()) || !is_scalar(&r.ty()) {
+            return Err(invalid_operands(&l, &r, span));
+        }
+        Ok(typed(kind(Box::new(l), Box::new(r)), span, Type::Int))
+    }
+
+    // the result is int (0 or 1)
+    fn comparison(
+        &mut self,
+        kind: fn(Box<AST>, Box<AST>) -> NodeKind,
+        l: AST,
+        r: AST,
+        span: Span,
+    ) -> Result<AST, Diagnostic> {
+        let (l, r) = (self.expr(l)?, self.expr(r)?);
+        let (lty, rty) = (l.ty().decay(), r.ty().decay());
+        match (&lty, &rty) {
+            (Type::Ptr(lbase), Type::Ptr(rbase)) => {
+                if lbase != rbase && **lbase != Type::Void && **rbase != Type::Void {
+                    let msg = format!(
+                        "comparison of distinct pointer types ('{}' and '{}')",
+                        lty, rty
+                    );
+                    self.warnings.push(Diagnostic::warning(
+                        DiagnosticKind::Semantic,
+                        "compare-distinct-pointer-types",
+                        &msg,
+                        span,
+                    ));
+                }
+            }
+            (Type::Ptr(_), int) | (int, Type::Ptr(_)) if int.is_integer() => {
+                let is_null = |ast: &AST| ast.ty().is_integer() && eval(ast) == Some(0);
+                if !is_null(&l) && !is_null(&r) {
+                    let msg = format!(
+                        "comparison between pointer and integer ('{}' and '{}')",
+                        lty, rty
+                    );
+                    self.warnings.push(Diagnostic::warning(
+                        DiagnosticKind::Semantic,
+                        "pointer-integer-compare",
+                        &msg,
+                        span,
+                    ));
+                }
+            }
+            _ => {
+                let ast = self.arithmetic(kind, l, r, span)?;
+                return Ok(typed(ast.kind(), span, Type::Int));
+            }
+        }
+        Ok(typed(kind(Box::new(l), Box::new(r)), span, Type::Int))
+    }
+}
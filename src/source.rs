@@ -0,0 +1,132 @@
+#[test]
+fn test_line_col() {
+    let mut map = SourceMap::new();
+    let id = map.add_file("test.c", String::from("int\n  a;\n\nb"));
+    let file = map.file(id);
+    assert_eq!(file.line_col(0), (1, 1));
+    assert_eq!(file.line_col(2), (1, 3));
+    assert_eq!(file.line_col(4), (2, 1));
+    assert_eq!(file.line_col(6), (2, 3));
+    assert_eq!(file.line_col(10), (4, 1));
+    assert_eq!(file.span(5, 6).to(file.span(7, 8)), file.span(5, 8));
+    assert_eq!(file.line(2), "  a;");
+    assert_eq!(file.line(3), "");
+    assert_eq!(file.line(4), "b");
+}
+
+// index of a file in the SourceMap
+pub type FileId = usize;
+
+// location of a construct in the source code
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize, // byte offset of the first character
+    pub end: usize,   // byte offset just after the last character
+    pub line: usize,  // line of start (1-origin)
+    pub col: usize,   // column of start in bytes (1-origin)
+}
+
+impl Span {
+    // empty span just after self (the token must not span lines)
+    pub fn end_point(&self) -> Span {
+        Span {
+            start: self.end,
+            col: self.col + (self.end - self.start),
+            ..*self
+        }
+    }
+
+    // empty span just before self
+    pub fn start_point(&self) -> Span {
+        Span {
+            end: self.start,
+            ..*self
+        }
+    }
+
+    // span from the start of self to the end of other
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub src: String,
+    line_starts: Vec<usize>, // byte offset of the beginning of each line
+}
+
+impl SourceFile {
+    fn new(id: FileId, name: &str, src: String) -> SourceFile {
+        let mut line_starts = vec![0];
+        for (i, c) in src.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        SourceFile {
+            id,
+            name: String::from(name),
+            src,
+            line_starts,
+        }
+    }
+
+    // convert the byte offset to (line, column), both 1-origin
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    // text of the line (1-origin) without the line terminator
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.src.len(),
+        };
+        self.src[start..end].trim_end_matches('\r')
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let (line, col) = self.line_col(start);
+        Span {
+            file: self.id,
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+}
+
+// all source files read in the compilation
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, src: String) -> FileId {
+        let id = self.files.len();
+        self.files.push(SourceFile::new(id, name, src));
+        id
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id]
+    }
+}
@@ -1,165 +1,315 @@
 #[allow(unused_imports)]
 use std::fmt;
 
-const KEYWORD: [&'static str; 7] = ["return", "if", "else", "for", "while", "int", "void"];
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::parse::Type;
+use crate::source::{SourceFile, Span};
+
+// C11 keywords
+#[rustfmt::skip]
+const KEYWORD: [&str; 44] = [
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "_Alignas", "_Alignof", "_Atomic", "_Bool",
+    "_Complex", "_Generic", "_Imaginary", "_Noreturn", "_Static_assert", "_Thread_local",
+];
+
+// C11 punctuators, the longer first so that the longest one is matched
+#[rustfmt::skip]
+const PUNCTUATOR: [&str; 54] = [
+    "%:%:", "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "<:", ":>", "<%", "%>", "%:", "[", "]",
+    "(", ")", "{", "}", ".", "&", "*", "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":",
+    ";", "=", ",", "#",
+];
 
 #[test]
 fn test_tokenize() {
-    let tokens = tokenize(String::from("if(a)a =1;else a = 1;"));
+    use crate::source::SourceMap;
+    let mut map = SourceMap::new();
+    let id = map.add_file("test.c", String::from("if(a)a =1;else a = 1;"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
     println!("{:?}", tokens);
 }
 
+#[test]
+fn test_keyword_and_punctuator() {
+    use crate::source::SourceMap;
+    let mut map = SourceMap::new();
+    let id = map.add_file("test.c", String::from("interval return4 a<<=b->c+++d<:0:>"));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let strings: Vec<&str> = tokens.iter().map(|t| t.string.as_str()).collect();
+    let expected = [
+        "interval", "return4", "a", "<<=", "b", "->", "c", "++", "+", "d", "[", "0", "]", "",
+    ];
+    assert_eq!(strings, expected);
+    assert_eq!(tokens[0].kind, TokenKind::Ident);
+    assert_eq!(tokens[10].span.end - tokens[10].span.start, 2);
+}
+
+#[test]
+fn test_translation_phases() {
+    use crate::source::SourceMap;
+    let mut map = SourceMap::new();
+    let src = "\u{feff}// line\r\nre\\\r\nturn /* block\n*/ 1 =\\\n= 2; // a \\\n b";
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let strings: Vec<&str> = tokens.iter().map(|t| t.string.as_str()).collect();
+    assert_eq!(strings, ["return", "1", "==", "2", ";", ""]);
+    assert_eq!((tokens[0].span.line, tokens[0].span.col), (2, 1));
+    assert_eq!((tokens[2].span.line, tokens[2].span.col), (4, 6));
+
+    let id = map.add_file("test.c", String::from("a /* b"));
+    assert!(tokenize(map.file(id), &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_number() {
+    use crate::source::SourceMap;
+    let mut map = SourceMap::new();
+    let src = "10 0x1F 017 0b101 10u 2147483648 0xffffffff 0x80000000u 10ll 0xffffffffffffffff";
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let nums: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+    let expected = [
+        TokenKind::Num(10, Type::Int),
+        TokenKind::Num(31, Type::Int),
+        TokenKind::Num(15, Type::Int),
+        TokenKind::Num(5, Type::Int),
+        TokenKind::Num(10, Type::UInt),
+        TokenKind::Num(2147483648, Type::Long),
+        TokenKind::Num(4294967295, Type::UInt),
+        TokenKind::Num(2147483648, Type::UInt),
+        TokenKind::Num(10, Type::Long),
+        TokenKind::Num(-1, Type::ULong),
+        TokenKind::Eof,
+    ];
+    assert_eq!(nums, expected);
+
+    for src in ["08", "0b2", "0x", "1lL", "1uu", "99999999999999999999"] {
+        let id = map.add_file("test.c", String::from(src));
+        assert!(tokenize(map.file(id), &mut Vec::new()).is_err());
+    }
+    let mut warnings = Vec::new();
+    let id = map.add_file("test.c", String::from("18446744073709551615"));
+    tokenize(map.file(id), &mut warnings).unwrap();
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn test_char_and_string() {
+    use crate::source::SourceMap;
+    let mut map = SourceMap::new();
+    let src = r#"'a' '\n' '\x41' '\077' '\'' '\xff' "a\tb\0c" "\"\\""#;
+    let id = map.add_file("test.c", String::from(src));
+    let tokens = tokenize(map.file(id), &mut Vec::new()).unwrap();
+    let kinds: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+    let expected = [
+        TokenKind::Num(97, Type::Int),
+        TokenKind::Num(10, Type::Int),
+        TokenKind::Num(65, Type::Int),
+        TokenKind::Num(63, Type::Int),
+        TokenKind::Num(39, Type::Int),
+        TokenKind::Num(-1, Type::Int),
+        TokenKind::Str(b"a\tb\0c".to_vec()),
+        TokenKind::Str(b"\"\\".to_vec()),
+        TokenKind::Eof,
+    ];
+    assert_eq!(kinds, expected);
+
+    for src in ["''", "'a", "\"abc\n\"", "'\\x100'", "'\\400'"] {
+        let id = map.add_file("test.c", String::from(src));
+        assert!(tokenize(map.file(id), &mut Vec::new()).is_err());
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
-    Reserved,   // keywords or punctuators
-    Num(isize), // integer literals(value)
-    Ident,      // identifiers(name) function name and variable name
-    Keyword,    // Keywords (return, if, ...)
-    Eof,        // end of the tokens
+    Reserved,       // keywords or punctuators
+    Num(i64, Type), // integer literals(value, type)
+    Str(Vec<u8>),   // string literals(contents without the terminating NUL)
+    Ident,          // identifiers(name) function name and variable name
+    Keyword,        // Keywords (return, if, ...)
+    Eof,            // end of the tokens
 }
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind, // Token kind
-    pos: usize,          // start positon of the token
+    pub span: Span,      // location of the token
     pub string: String,  // token string
 }
 
 #[derive(Debug)]
-struct Tokenizer {
+struct Tokenizer<'a> {
     pos: usize,
-    input: String,
+    input: &'a str,
+    file: &'a SourceFile,
+    warnings: Vec<Diagnostic>,
 }
 
-pub fn tokenize(input: String) -> Vec<Token> {
-    let mut tokens = Vec::new();
+// characters of the input with the line splices (backslash-newline) removed,
+// paired with their byte offsets in the input
+struct Chars<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Iterator for Chars<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.pos = skip_splices(self.input, self.pos);
+        let c = self.input[self.pos..].chars().next()?;
+        let pos = self.pos;
+        self.pos += c.len_utf8();
+        Some((pos, c))
+    }
+}
+
+// byte offset after the line splices at pos
+fn skip_splices(input: &str, mut pos: usize) -> usize {
+    loop {
+        if input[pos..].starts_with("\\\n") {
+            pos += 2;
+        } else if input[pos..].starts_with("\\\r\n") {
+            pos += 3;
+        } else {
+            return pos;
+        }
+    }
+}
+
+// the tokenizer handles the translation phases 1-3:
+// UTF-8 BOM and CRLF, line splicing, and comments
+// warnings are appended to warnings even if the tokenization fails
+pub fn tokenize(
+    file: &SourceFile,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Vec<Token>, Diagnostic> {
     let mut tokenizer = Tokenizer {
         pos: 0,
-        input: input,
+        input: &file.src,
+        file,
+        warnings: Vec::new(),
     };
+    if tokenizer.input.starts_with('\u{feff}') {
+        tokenizer.pos = '\u{feff}'.len_utf8();
+    }
+    let ret = tokenizer.tokens();
+    warnings.append(&mut tokenizer.warnings);
+    ret
+}
 
-    while !tokenizer.is_eof() {
-        tokenizer.read_whitespace();
-        if tokenizer.is_eof() {
-            break;
-        }
+impl Tokenizer<'_> {
+    // read all the tokens followed by Eof
+    fn tokens(&mut self) -> Result<Vec<Token>, Diagnostic> {
+        let mut tokens = Vec::new();
+        while !self.is_eof() {
+            self.read_whitespace()?;
+            if self.is_eof() {
+                break;
+            }
+            let start = self.pos;
 
-        // numeric literals
-        match tokenizer.next_char() {
-            '0'..='9' => {
-                tokens.push(Token {
-                    kind: TokenKind::Num(tokenizer.read_number()),
-                    pos: tokenizer.pos,
-                    string: String::new(),
-                });
+            // numeric literals
+            if let '0'..='9' = self.next_char() {
+                let (val, ty) = self.read_number()?;
+                tokens.push(self.new_token(TokenKind::Num(val, ty), start));
+                continue;
+            };
+
+            // character literals
+            if self.next_char() == '\'' {
+                let val = self.read_char_literal()?;
+                tokens.push(self.new_token(TokenKind::Num(val, Type::Int), start));
                 continue;
             }
-            _ => (),
-        };
 
-        // keywords (reserved words)
-        if tokenizer.is_keyword() {
-            tokens.push(tokenizer.read_keyword());
-            continue;
-        }
+            // string literals
+            if self.next_char() == '"' {
+                let bytes = self.read_quoted('"')?;
+                tokens.push(self.new_token(TokenKind::Str(bytes), start));
+                continue;
+            }
 
-        // identifiers
-        if tokenizer.is_al() {
-            let ident = tokenizer.read_ident();
-            tokens.push(Token {
-                kind: TokenKind::Ident,
-                pos: tokenizer.pos,
-                string: ident,
-            });
-            continue;
-        }
+            // identifiers or keywords (reserved words)
+            if self.is_al() {
+                let ident = self.read_ident();
+                let kind = if KEYWORD.contains(&ident.as_str()) {
+                    TokenKind::Keyword
+                } else {
+                    TokenKind::Ident
+                };
+                tokens.push(self.new_token(kind, start));
+                continue;
+            }
 
-        // punctuators
-        if tokenizer.starts_with("==")
-            || tokenizer.starts_with("!=")
-            || tokenizer.starts_with("<=")
-            || tokenizer.starts_with(">=")
-        {
-            let punc = tokenizer.read_nchars(2);
-            tokens.push(Token {
-                kind: TokenKind::Reserved,
-                pos: tokenizer.pos,
-                string: punc,
-            });
-            continue;
-        }
-        match tokenizer.next_char() {
-            '!' | '"' | '#' | '$' | '%' | '&' | '(' | ')' | '*' | '+' | ',' | '-' | '.' | '/'
-            | ':' | ';' | '<' | '=' | '>' | '?' | '@' | '[' | '\\' | ']' | '^' | '_' | '`'
-            | '{' | '|' | '}' | '~' => {
-                let punc = tokenizer.read_nchars(1);
-                tokens.push(Token {
-                    kind: TokenKind::Reserved,
-                    pos: tokenizer.pos,
-                    string: punc,
+            // punctuators
+            if let Some(punct) = PUNCTUATOR.iter().find(|p| self.starts_with(p)) {
+                self.read_nchars(punct.len());
+                let mut token = self.new_token(TokenKind::Reserved, start);
+                // digraphs are spelled as the punctuators they stand for
+                token.string = String::from(match *punct {
+                    "<:" => "[",
+                    ":>" => "]",
+                    "<%" => "{",
+                    "%>" => "}",
+                    "%:" => "#",
+                    "%:%:" => "##",
+                    p => p,
                 });
+                tokens.push(token);
                 continue;
             }
-            _ => (),
-        };
-        tokenizer.error_at(&format!("invalid token"));
-    }
-    tokens.push(Token {
-        kind: TokenKind::Eof,
-        pos: tokenizer.pos,
-        string: String::new(),
-    });
-    tokens
-}
-
-impl Tokenizer {
-    // read the next character
-    fn next_char(&self) -> char {
-        if self.is_eof() {
-            self.error_at(&format!("unexpected EOF"));
+            return Err(self.error_at("invalid-token", "invalid token"));
         }
-        self.input[self.pos..].chars().next().unwrap()
+        tokens.push(self.new_token(TokenKind::Eof, self.pos));
+        Ok(tokens)
     }
 
-    fn is_eof(&self) -> bool {
-        self.pos >= self.input.len()
+    // make the token which begins at start and ends at the current position
+    fn new_token(&self, kind: TokenKind, start: usize) -> Token {
+        Token {
+            kind,
+            span: self.file.span(start, self.pos),
+            string: self
+                .chars_from(start)
+                .take_while(|&(i, _)| i < self.pos)
+                .map(|(_, c)| c)
+                .collect(),
+        }
     }
 
-    fn is_al(&self) -> bool {
-        match self.next_char() {
-            'a'..='z' | 'A'..='Z' | '_' => true,
-            _ => false,
+    fn chars_from(&self, pos: usize) -> Chars<'_> {
+        Chars {
+            input: self.input,
+            pos,
         }
     }
 
-    // 先頭の文字がトークンを構成する文字(英数字or_)かを返す
-    fn is_alnum(&self) -> bool {
-        match self.next_char() {
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => true,
-            _ => false,
+    // read the next character ('\0' at the end of the input)
+    fn next_char(&self) -> char {
+        match self.chars_from(self.pos).next() {
+            Some((_, c)) => c,
+            None => '\0',
         }
     }
 
-    // check if the first string matches to the specified string
-    fn is_keyword(&mut self) -> bool {
-        if !self.is_al() {
-            return false;
-        }
-        for kw in KEYWORD {
-            if self.starts_with(kw) {
-                return true;
-            }
-        }
-        false
+    fn is_eof(&self) -> bool {
+        self.chars_from(self.pos).next().is_none()
+    }
+
+    fn is_al(&self) -> bool {
+        matches!(self.next_char(), 'a'..='z' | 'A'..='Z' | '_')
     }
 
     // read forward one character
     fn read_char(&mut self) -> char {
-        let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
-        self.pos += next_pos;
-        cur_char
+        let (pos, c) = self.chars_from(self.pos).next().unwrap();
+        self.pos = pos + c.len_utf8();
+        c
     }
 
     // read forward n characters
@@ -173,7 +323,9 @@ impl Tokenizer {
 
     // posの指す位置がが文字列で始まってるかを返す
     fn starts_with(&self, s: &str) -> bool {
-        self.input[self.pos..].starts_with(s)
+        let mut chars = self.chars_from(self.pos);
+        s.chars()
+            .all(|c| matches!(chars.next(), Some((_, d)) if c == d))
     }
 
     // read forward while the condition is satisfied
@@ -188,64 +340,301 @@ impl Tokenizer {
         result
     }
 
-    // read forward whitespaces and LF
-    fn read_whitespace(&mut self) {
-        self.read_while(char::is_whitespace);
+    // read forward whitespaces (including CR and LF) and comments
+    fn read_whitespace(&mut self) -> Result<(), Diagnostic> {
+        loop {
+            self.read_while(char::is_whitespace);
+            if self.starts_with("//") {
+                self.read_while(|c| c != '\n');
+            } else if self.starts_with("/*") {
+                self.read_block_comment()?;
+            } else {
+                break;
+            }
+        }
+        // the next token begins after the line splices
+        self.pos = skip_splices(self.input, self.pos);
+        Ok(())
+    }
+
+    // read forward /* ... */
+    fn read_block_comment(&mut self) -> Result<(), Diagnostic> {
+        let start = self.pos;
+        self.read_nchars(2);
+        while !self.starts_with("*/") {
+            if self.is_eof() {
+                let span = self.file.span(start, start + 2);
+                let diag = Diagnostic::new(
+                    DiagnosticKind::Lex,
+                    "unterminated-comment",
+                    "unterminated comment",
+                    Some(span),
+                );
+                return Err(diag.with_fixit(self.file.span(self.pos, self.pos), "*/"));
+            }
+            self.read_char();
+        }
+        self.read_nchars(2);
+        Ok(())
+    }
+
+    // read forward an integer literal (decimal, 0x hexadecimal, 0 octal or 0b binary)
+    // and decide its type by the value and the suffix
+    // the value of unsigned long is kept as the same bits in i64
+    fn read_number(&mut self) -> Result<(i64, Type), Diagnostic> {
+        let start = self.pos;
+        // the digits and the suffix are read at once, so 12abc is one token
+        let s = self.read_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let span = self.file.span(start, self.pos);
+        let error = |code: &'static str, msg: &str| {
+            Diagnostic::new(DiagnosticKind::Lex, code, msg, Some(span))
+        };
+
+        let lower = s.to_ascii_lowercase();
+        let (base, name, prefix_len) = if lower.starts_with("0x") {
+            (16, "hexadecimal", 2)
+        } else if lower.starts_with("0b") {
+            (2, "binary", 2)
+        } else if s.starts_with('0') {
+            (8, "octal", 1)
+        } else {
+            (10, "decimal", 0)
+        };
+        // decimal digits of a binary or an octal literal are reported as invalid digits
+        let is_digit = |c: char| c.is_digit(base) || (base < 10 && c.is_ascii_digit());
+        let digits_len = s[prefix_len..]
+            .find(|c| !is_digit(c))
+            .unwrap_or(s.len() - prefix_len);
+        let digits = &s[prefix_len..prefix_len + digits_len];
+        let suffix = &s[prefix_len + digits_len..];
+
+        if let Some(c) = digits.chars().find(|c| !c.is_digit(base)) {
+            return Err(error(
+                "invalid-digit",
+                &format!("invalid digit '{}' in {} constant", c, name),
+            ));
+        }
+        if digits.is_empty() && base != 8 {
+            let msg = format!("invalid suffix '{}' on integer constant", &s[1..]);
+            return Err(error("invalid-integer-suffix", &msg));
+        }
+        let (is_unsigned, longs) = match parse_suffix(suffix) {
+            Some(suffix) => suffix,
+            None => {
+                let msg = format!("invalid suffix '{}' on integer constant", suffix);
+                return Err(error("invalid-integer-suffix", &msg));
+            }
+        };
+        let val = match digits {
+            "" => 0, // "0" is read as the prefix of octal
+            _ => match u64::from_str_radix(digits, base) {
+                Ok(val) => val,
+                Err(_) => {
+                    let msg = "integer literal is too large to be represented in any integer type";
+                    return Err(error("integer-too-large", msg));
+                }
+            },
+        };
+
+        // candidates of the type in order (C11 6.4.4.1)
+        // long long is the same as long
+        let candidates: &[Type] = match (is_unsigned, longs, base == 10) {
+            (false, 0, true) => &[Type::Int, Type::Long],
+            (false, 0, false) => &[Type::Int, Type::UInt, Type::Long, Type::ULong],
+            (false, _, true) => &[Type::Long],
+            (false, _, false) => &[Type::Long, Type::ULong],
+            (true, 0, _) => &[Type::UInt, Type::ULong],
+            (true, _, _) => &[Type::ULong],
+        };
+        let fits = |ty: &Type| match ty {
+            Type::Int => val <= i32::MAX as u64,
+            Type::UInt => val <= u32::MAX as u64,
+            Type::Long => val <= i64::MAX as u64,
+            _ => true,
+        };
+        let ty = match candidates.iter().find(|ty| fits(ty)) {
+            Some(ty) => ty.clone(),
+            None => {
+                let msg = "integer literal is too large to be represented in a signed integer type, interpreting as unsigned";
+                self.warnings.push(Diagnostic::warning(
+                    DiagnosticKind::Lex,
+                    "implicitly-unsigned-literal",
+                    msg,
+                    span,
+                ));
+                Type::ULong
+            }
+        };
+        Ok((val as i64, ty))
+    }
+
+    // read forward a character literal
+    // the type is int, and a multi-character constant packs the bytes like gcc
+    fn read_char_literal(&mut self) -> Result<i64, Diagnostic> {
+        let start = self.pos;
+        let bytes = self.read_quoted('\'')?;
+        let span = self.file.span(start, self.pos);
+        match bytes.len() {
+            0 => Err(Diagnostic::new(
+                DiagnosticKind::Lex,
+                "empty-char-constant",
+                "empty character constant",
+                Some(span),
+            )),
+            // char is signed
+            1 => Ok(bytes[0] as i8 as i64),
+            _ => {
+                let msg = "multi-character character constant";
+                self.warnings.push(Diagnostic::warning(
+                    DiagnosticKind::Lex,
+                    "multichar",
+                    msg,
+                    span,
+                ));
+                let val = bytes.iter().fold(0, |val, &b| (val << 8) | b as i32);
+                Ok(val as i64)
+            }
+        }
+    }
+
+    // read forward the characters enclosed in quote (' or ") and return their bytes
+    fn read_quoted(&mut self, quote: char) -> Result<Vec<u8>, Diagnostic> {
+        let start = self.pos;
+        self.read_char();
+        let mut bytes = Vec::new();
+        while self.next_char() != quote {
+            if self.is_eof() || self.next_char() == '\n' {
+                let msg = format!("missing terminating {} character", quote);
+                let span = self.file.span(start, start + 1);
+                return Err(Diagnostic::new(
+                    DiagnosticKind::Lex,
+                    "missing-terminating-char",
+                    &msg,
+                    Some(span),
+                ));
+            }
+            if self.next_char() == '\\' {
+                self.read_escape(&mut bytes)?;
+            } else {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(self.read_char().encode_utf8(&mut buf).as_bytes());
+            }
+        }
+        self.read_char();
+        Ok(bytes)
     }
 
-    // read forward non-negative integer
-    fn read_number(&mut self) -> isize {
-        let s = self.read_while(|c| match c {
-            '0'..='9' => true,
-            _ => false,
-        });
-        match s.parse::<isize>() {
-            Ok(i) => {
-                return i;
+    // read forward an escape sequence and push the byte it stands for
+    fn read_escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), Diagnostic> {
+        let start = self.pos;
+        self.read_char();
+        if self.is_eof() {
+            return Ok(());
+        }
+        let error = |tokenizer: &Self, code: &'static str, msg: &str| {
+            let span = tokenizer.file.span(start, tokenizer.pos);
+            Diagnostic::new(DiagnosticKind::Lex, code, msg, Some(span))
+        };
+        let c = self.read_char();
+        let byte = match c {
+            'a' => 7,
+            'b' => 8,
+            't' => 9,
+            'n' => 10,
+            'v' => 11,
+            'f' => 12,
+            'r' => 13,
+            'e' => 27, // GNU extension
+            '\'' | '"' | '?' | '\\' => c as u8,
+            // octal: up to 3 digits
+            '0'..='7' => {
+                let mut val = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match self.next_char().to_digit(8) {
+                        Some(d) => {
+                            self.read_char();
+                            val = val * 8 + d;
+                        }
+                        None => break,
+                    }
+                }
+                if val > 0xff {
+                    return Err(error(
+                        self,
+                        "octal-escape-out-of-range",
+                        "octal escape sequence out of range",
+                    ));
+                }
+                val as u8
+            }
+            // hexadecimal: any number of digits
+            'x' => {
+                let digits = self.read_while(|c| c.is_ascii_hexdigit());
+                if digits.is_empty() {
+                    return Err(error(
+                        self,
+                        "missing-hex-digits",
+                        "\\x used with no following hex digits",
+                    ));
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(error(
+                            self,
+                            "hex-escape-out-of-range",
+                            "hex escape sequence out of range",
+                        ))
+                    }
+                }
             }
-            Err(_) => {
-                self.error_at(&format!("invalid number"));
-                return 0;
+            _ => {
+                let msg = format!("unknown escape sequence '\\{}'", c);
+                let warning = Diagnostic::warning(
+                    DiagnosticKind::Lex,
+                    "unknown-escape-sequence",
+                    &msg,
+                    self.file.span(start, self.pos),
+                );
+                self.warnings.push(warning);
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                return Ok(());
             }
         };
+        bytes.push(byte);
+        Ok(())
     }
 
     fn read_ident(&mut self) -> String {
         if !self.is_al() {
             panic!("variable name must begin with alphabet or underscore");
         }
-        let s = self.read_while(|c| match c {
-            'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => true,
-            _ => false,
-        });
-        s
-    }
-
-    // read forward keywords
-    // Todo return4;なども正しい入力と見做されることに注意
-    fn read_keyword(&mut self) -> Token {
-        for i in 0..KEYWORD.len() {
-            if self.starts_with(KEYWORD[i]) {
-                let keyword = self.read_nchars(KEYWORD[i].len());
-                return Token {
-                    kind: TokenKind::Keyword,
-                    pos: self.pos,
-                    string: keyword,
-                };
-            }
-        }
-        self.error_at(&format!("keyword is expected"));
-        panic!("");
+
+        self.read_while(|c| c.is_ascii_alphanumeric() || c == '_')
     }
 
-    fn error_at(&self, string: &str) {
-        println!("{}", self.input);
-        print!("{}", " ".repeat(self.pos));
-        println!("^ ");
-        print!("{}", " ".repeat(self.pos));
-        println!("{}", string);
-        println!("");
+    fn error_at(&self, code: &'static str, string: &str) -> Diagnostic {
+        let end = self.pos + self.next_char().len_utf8();
+        let span = self.file.span(self.pos, end);
+        Diagnostic::new(DiagnosticKind::Lex, code, string, Some(span))
+    }
+}
 
-        panic!("invalid input at character: {}", self.pos);
+// integer suffix to (is unsigned, the number of l)
+// u can be either before or after l, and ll must be the same case
+fn parse_suffix(suffix: &str) -> Option<(bool, usize)> {
+    let (is_unsigned, longs) = if let Some(s) = suffix.strip_prefix(['u', 'U']) {
+        (true, s)
+    } else if let Some(s) = suffix.strip_suffix(['u', 'U']) {
+        (true, s)
+    } else {
+        (false, suffix)
+    };
+    match longs {
+        "" => Some((is_unsigned, 0)),
+        "l" | "L" => Some((is_unsigned, 1)),
+        "ll" | "LL" => Some((is_unsigned, 2)),
+        _ => None,
     }
 }
@@ -7,8 +7,7 @@ assert() {
     input="$2"
 
     echo "$input" > "${DEBUG}"tmp.src
-    "${DEBUG}"ccr -d "${DEBUG}tmp.src"
-    cc -o ${DEBUG}tmp "${DEBUG}"tmp.s
+    "${DEBUG}"ccr -d -o "${DEBUG}"tmp "${DEBUG}"tmp.src
     ${DEBUG}tmp
     actual="$?"
     
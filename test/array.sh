@@ -0,0 +1,42 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+assert 3 'int main(void) { int a[4]; a[0] = 1; a[3] = 2; return a[0] + a[3]; }'
+assert 45 'int main(void) { int a[10]; for (int i = 0; i < 10; i = i + 1) a[i] = i; int s = 0; for (int i = 0; i < 10; i = i + 1) s = s + a[i]; return s; }'
+assert 5 'int main(void) { int a[3]; 2[a] = 5; return a[2]; }'
+assert 23 'int main(void) { int m[3][4]; for (int i = 0; i < 3; i = i + 1) for (int j = 0; j < 4; j = j + 1) m[i][j] = i * 10 + j; return m[2][3]; }'
+assert 12 'int main(void) { int m[3][4]; m[1][2] = 12; int *p = m[1]; return p[2]; }'
+assert 7 'int main(void) { int a[2]; int *p = a; p[1] = 7; return a[1]; }'
+assert 9 'int main(void) { int a[3]; int b = 4; a[0] = 5; a[2] = 0; return a[0] + b; }'
+assert 4 'int main(void) { char s[4]; s[0] = 1; s[3] = 3; return s[0] + s[3]; }'
+assert 98 'int main(void) { return "abc"[1]; }'
+assert 8 'int g[5]; int main(void) { g[4] = 8; return g[4]; }'
+assert 10 'int sum(int *p, int n) { int s = 0; for (int i = 0; i < n; i = i + 1) s = s + p[i]; return s; } int main(void) { int a[4]; for (int i = 0; i < 4; i = i + 1) a[i] = i + 1; return sum(a, 4); }'
+assert 6 'int get(int a[], int i) { return a[i]; } int main(void) { int a[3]; a[1] = 6; return get(a, 1); }'
+assert 11 'int get(int m[][4]) { return m[2][3]; } int main(void) { int m[3][4]; m[2][3] = 11; return get(m); }'
+assert 2 'int main(void) { int a[2][2]; a[1][0] = 2; return *&a[1][0]; }'
+
+assert 16 'int main(void) { char a[-1u >> 28]; return sizeof(a) + 1; }'
+
+echo OK
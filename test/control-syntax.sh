@@ -8,8 +8,7 @@ assert() {
     input="$2"
 
     echo "$input" > "${DEBUG}"tmp.src
-    "${DEBUG}"ccr -q "${DEBUG}tmp.src"
-    cc -o ${DEBUG}tmp "${DEBUG}"tmp.s
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
     ${DEBUG}tmp
     actual="$?"
     
@@ -28,4 +27,17 @@ assert 200 'int main(void) { int flag = 1; if (flag == 1) { return 200; } else r
 assert 200 'int main(void) { if (50 >= 50) { return 200; } else return 100; }'
 assert 100 'int main(void) { if (30 > 30) { return 200; } else return 100; }'
 
+# block scopes
+assert 3 'int main(void) { int a = 1; { int a = 2; a = a + 1; } return a + 2; }'
+assert 5 'int main(void) { int a = 1; { int a = 2; { int a = 3; } a = a + 3; return a; } }'
+assert 7 'int main(void) { int a = 3; { int b = 4; a = a + b; } { int b = 10; } return a; }'
+assert 4 'int a = 4; int main(void) { { int a = 1; } return a; }'
+assert 6 'int f(int a) { { int a = 5; } return a; } int main(void) { return f(6); }'
+
+# declarations in for
+assert 45 'int main(void) { int sum = 0; for (int i = 0; i < 10; i = i + 1) sum = sum + i; return sum; }'
+assert 3 'int main(void) { int i = 3; for (int i = 0; i < 10; i = i + 1) { int i = 5; } return i; }'
+assert 6 'int main(void) { int s = 0; for (int i = 0, j = 3; i < j; i = i + 1) s = s + j; for (int i = 0; i < 0;) {} return s - 3; }'
+assert 0 'int main(void) { int n = 0; for (int i = 0; i < 3000000; i = i + 1) n = n + 1; return n - 3000000; }'
+
 echo OK
\ No newline at end of file
@@ -8,8 +8,7 @@ assert() {
     input="$2"
 
     echo "$input" > "${DEBUG}"tmp.src
-    "${DEBUG}"ccr -q "${DEBUG}tmp.src"
-    cc -o ${DEBUG}tmp "${DEBUG}"tmp.s
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
     ${DEBUG}tmp
     actual="$?"
     
@@ -28,4 +27,140 @@ assert 7 'int main(void) {return 10 - 5 + 2;}'
 assert 100 'int main(void) {return (1000*31 -1000)/300;}'
 assert  10 'int main(void) {return ((40-38)*2 + 50)/6 + 1;}'
 
+# comments, line splices, CRLF and BOM
+assert 3 'int main(void) { // comment
+return 3; }'
+assert 5 'int main(void) { /* multi
+line */ return /**/ 5; }'
+assert 42 'int main(void) { ret\
+urn 4\
+2; }'
+assert 1 $'int main(void) {\r\n  return 1 =\\\r\n= 1;\r\n}\r\n'
+assert 7 $'\xef\xbb\xbfint main(void) { return 7; }'
+
+# keywords only at identifier boundaries
+assert 3 'int main(void) { int interval; interval = 3; return interval; }'
+assert 4 'int main(void) { int return4; return4 = 4; return return4; }'
+assert 5 'int main(void) { int if_; int _while; if_ = 2; _while = 3; return if_ + _while; }'
+
+# integer literals
+assert 31 'int main(void) { return 0x1f; }'
+assert 15 'int main(void) { return 017; }'
+assert 5 'int main(void) { return 0b101; }'
+assert 10 'int main(void) { return 10u + 0L - 0ull; }'
+assert 3 'int main(void) { return 0x100000003 - 0x100000000; }'
+assert 2 'int main(void) { return 8589934592 / 4294967296; }'
+assert 1 'int main(void) { return 9223372036854775807 == 0x7fffffffffffffff; }'
+
+# modulo
+assert 1 'int main(void) { return 10 % 3; }'
+assert 0 'int main(void) { return 9 % 3; }'
+assert 255 'int main(void) { return -7 % 3; }'
+assert 1 'int main(void) { return 7 % -3; }'
+assert 1 'int main(void) { return 4294967295u % 7 - 2; }'
+assert 1 'int main(void) { return 2 + 3 * 4 % 5 == 4; }'
+
+# bitwise operators
+assert 2 'int main(void) { return 6 & 3; }'
+assert 7 'int main(void) { return 6 | 3; }'
+assert 5 'int main(void) { return 6 ^ 3; }'
+assert 1 'int main(void) { return ~0 == -1; }'
+assert 250 'int main(void) { return ~5 & 255; }'
+assert 1 'int main(void) { unsigned u = ~0u; return u == 4294967295u; }'
+assert 1 'int main(void) { char c = 1; return sizeof(~c) == 4; }'
+assert 1 'int main(void) { char c = 1; return sizeof(+c) == 4 && +c == 1; }'
+assert 5 'int main(void) { return 1 | 2 ^ 3 & 6 | 4; }'
+assert 1 'int main(void) { return (5 & 4) == 4; }'
+assert 1 'int main(void) { return 5 & 4 == 4; }'
+
+# shifts
+assert 16 'int main(void) { return 1 << 4; }'
+assert 4 'int main(void) { return 64 >> 4; }'
+assert 1 'int main(void) { return -16 >> 2 == -4; }'
+assert 1 'int main(void) { unsigned u = 4294967280u; return u >> 2 == 1073741820u; }'
+assert 1 'int main(void) { int x = 1 << 31; return x < 0; }'
+assert 1 'int main(void) { long l = 1L << 40; return l >> 40; }'
+assert 1 'int main(void) { char c = 1; return sizeof(c << 1) == 4 && sizeof(1 << 1L) == 4; }'
+assert 12 'int main(void) { return 1 + 2 << 2; }'
+assert 1 'int main(void) { return 1 << 3 > 7; }'
+assert 255 'int main(void) { unsigned char c = 255; return c >> 0; }'
+
+# bit manipulation
+assert 1 'int main(void) { unsigned h = 2166136261u; h = (h ^ 97) * 16777619u; return h == 3826002220u; }'
+assert 3 'int main(void) { int x = 0; int i; for (i = 0; i < 8; i = i + 1) if (0xb0 >> i & 1) x = x + 1; return x; }'
+assert 5 'int main(void) { enum { READ = 1 << 0, WRITE = 1 << 1, EXEC = 1 << 2 }; int flags = READ | EXEC; return flags & ~WRITE; }'
+
+# logical operators
+assert 1 'int main(void) { return 1 && 2; }'
+assert 0 'int main(void) { return 1 && 0; }'
+assert 0 'int main(void) { return 0 && 1; }'
+assert 1 'int main(void) { return 0 || 3; }'
+assert 0 'int main(void) { return 0 || 0; }'
+assert 1 'int main(void) { return !0; }'
+assert 0 'int main(void) { return !5; }'
+assert 2 'int main(void) { return !!7 + !!9; }'
+assert 1 'int main(void) { return 1 || 0 && 0; }'
+assert 0 'int main(void) { return (1 || 0) && 0; }'
+assert 1 'int main(void) { return 1 < 2 && 3 != 4; }'
+assert 1 'int main(void) { return 8589934592 && 1; }'
+assert 0 'int main(void) { return !8589934592; }'
+assert 6 'int main(void) { return -!0 + 7; }'
+
+# short-circuit evaluation
+assert 0 'int main(void) { int x = 0; 0 && (x = 1); return x; }'
+assert 1 'int main(void) { int x = 0; 1 && (x = 1); return x; }'
+assert 0 'int main(void) { int x = 0; 1 || (x = 1); return x; }'
+assert 1 'int main(void) { int x = 0; 0 || (x = 1); return x; }'
+assert 0 'int main(void) { int *p = 0; return p && *p; }'
+assert 5 'int main(void) { int a = 5; int *p = &a; if (p && *p == 5) return *p; return 0; }'
+assert 1 'int main(void) { int *p = 0; return !p || *p; }'
+assert 3 'int main(void) { char *s = "abc"; int n = 0; for (; s && *s; s = s + 1) n = n + 1; return n; }'
+assert 3 'int main(void) { enum { A = 1 && 2, B = !A || 1, C = A + B + !0 }; return C; }'
+
+# compound assignment
+assert 7 'int main(void) { int a = 5; a += 2; return a; }'
+assert 3 'int main(void) { int a = 5; a -= 2; return a; }'
+assert 15 'int main(void) { int a = 5; a *= 3; return a; }'
+assert 2 'int main(void) { int a = 5; a /= 2; return a; }'
+assert 1 'int main(void) { int a = 5; a %= 2; return a; }'
+assert 4 'int main(void) { int a = 6; a &= 12; return a; }'
+assert 14 'int main(void) { int a = 6; a |= 12; return a; }'
+assert 10 'int main(void) { int a = 6; a ^= 12; return a; }'
+assert 40 'int main(void) { int a = 5; a <<= 3; return a; }'
+assert 252 'int main(void) { int a = -16; a >>= 2; return a; }'
+assert 9 'int main(void) { int a = 5; int b; b = a += 4; return b; }'
+assert 12 'int main(void) { int a = 2; int b = 3; a += b *= 2; return a + b - 2; }'
+assert 44 'int main(void) { char c = 300; c += 0; return c; }'
+assert 0 'int main(void) { char c = 127; c += 129; return c; }'
+assert 3 'int main(void) { int a[4]; int *p = a; p += 3; return p - a; }'
+assert 1 'int main(void) { long a[4]; long *p = a + 3; p -= 2; return p - a; }'
+assert 8 'int main(void) { int a[3]; a[0] = 1; a[1] = 2; a[2] = 3; int i = 1; a[i] += 1; a[i + 1] *= 2; return a[0] + a[1] + a[2] - 2; }'
+assert 8 'int main(void) { struct { int x; } s; s.x = 3; s.x += 5; return s.x; }'
+assert 1 'int main(void) { unsigned u = 0; u -= 1; return u == 4294967295u; }'
+
+# increment and decrement
+assert 6 'int main(void) { int a = 5; ++a; return a; }'
+assert 6 'int main(void) { int a = 5; a++; return a; }'
+assert 4 'int main(void) { int a = 5; return --a; }'
+assert 5 'int main(void) { int a = 5; return a--; }'
+assert 11 'int main(void) { int a = 5; int b = a++; return a + b; }'
+assert 12 'int main(void) { int a = 5; int b = ++a; return a + b; }'
+assert 127 'int main(void) { char c = 127; return c++; }'
+assert 128 'int main(void) { char c = 127; c++; return c; }'
+assert 255 'int main(void) { unsigned char c = 0; c--; return c; }'
+assert 0 'int main(void) { unsigned char c = 0; return c--; }'
+assert 2 'int main(void) { int a[3]; int *p = a; p++; ++p; return p - a; }'
+assert 3 'int main(void) { int a[3]; a[0] = 1; a[1] = 3; int *p = a; p++; return *p; }'
+assert 1 'int main(void) { int a[3]; a[0] = 1; a[1] = 3; int *p = a; return *p++; }'
+assert 4 'int main(void) { int a[2]; a[0] = 3; int *p = a; ++*p; return a[0]; }'
+assert 10 'int main(void) { int s = 0; int i; for (i = 0; i < 5; i++) s += i; return s; }'
+assert 3 'int main(void) { char *s = "abc"; int n = 0; while (*s++) n++; return n; }'
+
+# the lvalue is evaluated only once
+assert 1 'int n; int f(void) { n++; return 1; } int main(void) { int a[2]; a[1] = 0; a[f()] += 1; return n; }'
+assert 13 'int n; int f(void) { n++; return 1; } int main(void) { int a[2]; a[1] = 2; a[f()]++; return n * 10 + a[1]; }'
+assert 2 'int main(void) { int a[3]; int *p = a; *p++ = 1; *p++ = 2; return p - a; }'
+assert 15 'int main(void) { int i = 0; int a[2]; a[0] = 0; a[i++] += 5; return i * 10 + a[0]; }'
+assert 33 'int main(void) { int a[3]; a[1] = 2; a[2] = 3; int *p = a; (*++p)++; return *p * 10 + p[1]; }'
+
 echo OK
@@ -0,0 +1,181 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+# check that the diagnostics printed in the format contain the pattern
+assert() {
+    format="$1"
+    pattern="$2"
+    input="$3"
+
+    echo "$input" > "${DEBUG}"tmp.c
+    actual=$("${DEBUG}"ccr -q --color=never --diagnostics-format="$format" -o "${DEBUG}"tmp "${DEBUG}"tmp.c 2>&1)
+
+    if echo "$actual" | grep -qF -- "$pattern"; then
+        echo -n "."
+    else
+        echo -e "\n'$pattern' is expected, but got:\n$actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+
+    # every diagnostic must have a stable code, whatever its message is
+    json=$("${DEBUG}"ccr -q --diagnostics-format=json -o "${DEBUG}"tmp "${DEBUG}"tmp.c 2>&1)
+    records=$(echo "$json" | grep -o '"severity": ' | wc -l)
+    codes=$(echo "$json" | grep -oE '"severity": "[a-z]+", "code": "[a-z0-9-]+"' | wc -l)
+    if [ "$records" != "$codes" ]; then
+        echo -e "\nevery diagnostic is expected to have a code, but got:\n$json"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+assert text "error: expected ';' [syntax]" 'int main(void) { return 0 }'
+assert text "warning: if statement has empty body [semantic]" 'int main(void) { if (1); return 0; }'
+assert json "\"severity\": \"error\", \"code\": \"expected-token\", \"category\": \"syntax\", \"message\": \"expected ';'\"" 'int main(void) { return 0 }'
+assert json '"fixits": [{"span": {"file": "'"${DEBUG}"'tmp.c", "line": 1, "column": 26' 'int main(void) { return 0 }'
+assert json '"severity": "warning", "code": "empty-body", "category": "semantic"' 'int main(void) { while (0); return 0; }'
+assert json '"code": "redefinition", "category": "semantic"' 'int x = 1; int x = 2; int main(void) { return 0; }'
+assert json '"code": "redefinition-different-kind", "category": "semantic"' 'int f; int f(void); int main(void) { return 0; }'
+assert json "\"secondary\": [{\"message\": \"to match this '('\"" 'int main(void) { return (1; }'
+assert text "tmp.c:2:3: error: unterminated comment [lexical]" 'int main(void) {
+  /* comment
+  return 0; }'
+assert text "error: invalid digit '8' in octal constant [lexical]" 'int main(void) { return 08; }'
+assert text "error: invalid suffix 'lL' on integer constant [lexical]" 'int main(void) { return 1lL; }'
+assert text "error: integer literal is too large to be represented in any integer type [lexical]" 'int main(void) { return 18446744073709551616; }'
+assert text "warning: integer literal is too large to be represented in a signed integer type, interpreting as unsigned [lexical]" 'int main(void) { return 18446744073709551615; }'
+assert text "error: empty character constant [lexical]" "int main(void) { return ''; }"
+assert text "error: missing terminating \" character [lexical]" 'int main(void) { return *"abc; }'
+assert text "warning: unknown escape sequence '\\q' [lexical]" 'int main(void) { return *"\q"; }'
+assert text "error: redefinition of 'x' [semantic]" 'int x = 1; int x = 2; int main(void) { return 0; }'
+assert text "note: previous declaration is here" 'int x; char x; int main(void) { return 0; }'
+assert text "error: initializer element is not a compile-time constant [semantic]" 'int x; int y = x; int main(void) { return 0; }'
+assert text "error: 'b' is not defined [semantic]" 'int main(void) { { int b = 1; } return b; }'
+assert text "tmp.c:1:33: error: redefinition of 'a' [semantic]" 'int main(void) { int a = 1; int a = 2; return a; }'
+assert text "tmp.c:1:22: note: previous definition is here" 'int main(void) { int a = 1; int a = 2; return a; }'
+assert text "error: redefinition of 'a' [semantic]" 'int f(int a) { int a; return 0; } int main(void) { return 0; }'
+assert text "error: 'i' is not defined [semantic]" 'int main(void) { for (int i = 0; i < 3; i = i + 1) ; return i; }'
+assert text "warning: implicit declaration of function 'f' is invalid in C99 [semantic]" 'int main(void) { return f(); }'
+assert text "error: too many arguments to function call, expected 1, have 2 [semantic]" 'int f(int a); int main(void) { return f(1, 2); }'
+assert text "error: too few arguments to function call, expected 2, have 1 [semantic]" 'int f(int a, int b); int main(void) { return f(1); }'
+assert text "tmp.c:1:5: note: 'f' declared here" 'int f(void); int main(void) { return f(1); }'
+assert text "error: incompatible integer to pointer conversion passing 'int' to parameter of type 'char *' [semantic]" 'int f(char *s); int main(void) { return f(1); }'
+assert text "warning: incompatible pointer types passing 'int *' to parameter of type 'char *' [semantic]" 'int f(char *s); int main(void) { int a; return f(&a); }'
+assert text "error: conflicting types for 'f' [semantic]" 'int f(int a); char f(int a); int main(void) { return 0; }'
+assert text "error: conflicting types for 'x' [semantic]" 'int x; int main(void) { extern char x; return 0; }'
+assert text "error: declaration of block scope identifier with linkage cannot have an initializer [semantic]" 'int main(void) { extern int x = 1; return x; }'
+assert text "error: conflicting types for 'f' [semantic]" 'int f(int a); int f(char *s) { return 0; } int main(void) { return 0; }'
+assert text "error: redefinition of 'f' [semantic]" 'int f(void) { return 0; } int f(void) { return 1; } int main(void) { return 0; }'
+assert text "error: redefinition of 'f' as different kind of symbol [semantic]" 'int f; int f(void); int main(void) { return 0; }'
+assert text "error: called object type 'int' is not a function or function pointer [semantic]" 'int main(void) { int f; return f(); }'
+assert text "error: non-void function 'main' should return a value [semantic]" 'int main(void) { return; }'
+assert text "error: void function 'f' should not return a value [semantic]" 'void f(void) { return 1; } int main(void) { return 0; }'
+assert text "error: incompatible pointer to integer conversion returning 'int *' from a function with result type 'int' [semantic]" 'int main(void) { int a; return &a; }'
+assert text "error: parameter name omitted [syntax]" 'int f(int) { return 0; } int main(void) { return 0; }'
+assert text "error: array type 'int[3]' is not assignable [semantic]" 'int main(void) { int a[3]; int b[3]; a = b; return 0; }'
+assert text "error: subscripted value is not an array or pointer [semantic]" 'int main(void) { int a; return a[0]; }'
+assert text "error: array subscript is not an integer [semantic]" 'int main(void) { int a[2]; int *p; return a[p]; }'
+assert text "error: array size is not a constant [semantic]" 'int main(void) { int n = 3; int a[n]; return 0; }'
+assert text "error: array has negative size [semantic]" 'int main(void) { int a[-1]; return 0; }'
+assert text "warning: incompatible pointer types passing 'int[3][4]' to parameter of type 'int *' [semantic]" 'int f(int *p); int main(void) { int m[3][4]; return f(m); }'
+assert text "error: 'char' cannot be combined with the previous declaration specifiers [syntax]" 'int main(void) { long char c; return 0; }'
+assert text "error: 'signed' cannot be combined with the previous declaration specifiers [syntax]" 'int main(void) { unsigned signed x; return 0; }'
+assert text "error: invalid application of 'sizeof' to an incomplete type 'void' [semantic]" 'int main(void) { return sizeof(void); }'
+assert text "error: indirection requires pointer operand ('int' invalid) [semantic]" 'int main(void) { return sizeof(*5); }'
+assert text "error: invalid operands to binary expression ('int *' and 'int *') [semantic]" 'int main(void) { int *p, *q; return sizeof(p + q); }'
+assert text "error: expected a type [syntax]" 'int main(void) { int x; return _Alignof x; }'
+assert text "tmp.c:1:36: error: expected ')' [syntax]" 'int main(void) { return sizeof(int x); }'
+assert text "tmp.c:1:26: error: expected an expression [syntax]" 'int main(void) { int a = ; return 0; }'
+assert text "tmp.c:1:31: error: expected an expression [syntax]" 'int main(void) { for (int i = ; i < 3; i = i + 1) ; return 0; }'
+assert text "tmp.c:1:24: error: expected an expression [syntax]" 'int main(void) { int a[;]; return 0; }'
+assert text "tmp.c:1:7: error: expected an expression [syntax]" 'int a[;]; int main(void) { return 0; }'
+assert text "error: invalid operands to binary expression ('int *' and 'int *') [semantic]" 'int main(void) { int a; int *p = &a; p + p; return 0; }'
+assert text "error: invalid operands to binary expression ('int' and 'int *') [semantic]" 'int main(void) { int a; return 1 - &a; }'
+assert text "error: 'int *' and 'char *' are not pointers to compatible types [semantic]" 'int main(void) { int a; char c; return &a - &c; }'
+assert text "error: arithmetic on a pointer to an incomplete type 'struct s' [semantic]" 'struct s; struct s *p = 0; int main(void) { p = p + 1; return 0; }'
+assert text "error: arithmetic on a pointer to an incomplete type 'struct s' [semantic]" 'struct s; struct s *p = 0; int main(void) { return p - p; }'
+assert text "error: arithmetic on a pointer to an incomplete type 'void' [semantic]" 'int main(void) { void *p = 0; p = 1 + p; return 0; }'
+assert text "error: expression is not assignable [semantic]" 'int main(void) { 1 = 2; return 0; }'
+assert text "error: cannot take the address of an rvalue of type 'int' [semantic]" 'int main(void) { int a; &(a + 1); return 0; }'
+assert text "error: indirection requires pointer operand ('int' invalid) [semantic]" 'int main(void) { int a; return *a; }'
+assert text "error: statement requires expression of scalar type ('void' invalid) [semantic]" 'void f(void) { } int main(void) { if (f()) return 1; return 0; }'
+assert text "error: incompatible integer to pointer conversion assigning to 'int *' from 'int' [semantic]" 'int main(void) { int a; int *p = a; return 0; }'
+assert text "warning: incompatible pointer types assigning to 'int *' from 'char *' [semantic]" 'int main(void) { char *p; int *q = p; return 0; }'
+assert text "warning: comparison between pointer and integer ('int *' and 'int') [semantic]" 'int main(void) { int *p; return p == 1; }'
+assert text "warning: comparison of distinct pointer types ('int *' and 'char *') [semantic]" 'int main(void) { int *p; char *q; return p == q; }'
+assert text "error: invalid operands to binary expression ('int *' and 'int') [semantic]" 'int main(void) { int *p; return p * 2; }'
+assert text "error: no member named 'z' in 'struct point' [semantic]" 'struct point { int x, y; }; int main(void) { struct point p; return p.z; }'
+assert text "error: member reference base type 'int' is not a structure or union [semantic]" 'int main(void) { int a; return a.x; }'
+assert text "error: member reference type 'struct <anonymous>' is not a pointer [semantic]" 'int main(void) { struct { int x; } s; return s->x; }'
+assert text "error: member reference base type 'long' is not a structure or union [semantic]" 'int main(void) { int *p, *q; return (p - q).x; }'
+assert text "error: indirection requires pointer operand ('int' invalid) [semantic]" 'int main(void) { return (*5)->x; }'
+assert text "error: incomplete definition of type 'struct s' [semantic]" 'struct s; int main(void) { struct s *p; return p->x; }'
+assert text "error: variable has incomplete type 'struct s' [semantic]" 'int main(void) { struct s x; return 0; }'
+assert text "error: invalid application of 'sizeof' to an incomplete type 'union u' [semantic]" 'int main(void) { return sizeof(union u); }'
+assert text "error: duplicate member 'a' [semantic]" 'int main(void) { struct { int a; char a; } x; return 0; }'
+assert text "error: field has incomplete type 'struct s' [semantic]" 'int main(void) { struct s { struct s in; } x; return 0; }'
+assert text "error: redefinition of 's' [semantic]" 'struct s { int a; }; struct s { int b; }; int main(void) { return 0; }'
+assert text "error: use of 's' with tag type that does not match previous declaration [semantic]" 'struct s { int a; }; int main(void) { union s *p; return 0; }'
+assert text "warning: declaration does not declare anything [semantic]" 'int main(void) { struct { struct s { int a; }; int b; } x; return 0; }'
+assert text "error: incompatible types assigning to 'struct a' from 'struct b' [semantic]" 'int main(void) { struct a { int x; } a; struct b { int x; } b; a = b; return 0; }'
+assert text "error: statement requires expression of scalar type ('struct s' invalid) [semantic]" 'int main(void) { struct s { int a; } x; if (x) return 1; return 0; }'
+assert text "error: passing a struct by value is not supported [semantic]" 'struct s { int a; }; int f(struct s x) { return 0; } int main(void) { return 0; }'
+assert text "error: enumerator value is not a constant [semantic]" 'int main(void) { int n; enum { A = n }; return 0; }'
+assert text "error: redefinition of enumerator 'A' [semantic]" 'enum { A, B }; enum { C, A }; int main(void) { return 0; }'
+assert text "error: redefinition of 'x' as different kind of symbol [semantic]" 'int x; enum { x }; int main(void) { return 0; }'
+assert text "error: redefinition of 'A' [semantic]" 'int main(void) { enum { A }; int A; return 0; }'
+assert text "error: redefinition of 'e' [semantic]" 'enum e { A }; enum e { B }; int main(void) { return 0; }'
+assert text "error: use of 'e' with tag type that does not match previous declaration [semantic]" 'enum e { A }; struct e *p; int main(void) { return 0; }'
+assert text "error: ISO C restricts enumerator values to range of 'int' [semantic]" 'enum { A = 2147483647, B }; int main(void) { return 0; }'
+assert text "error: called object type 'int' is not a function or function pointer [semantic]" 'enum { A }; int main(void) { return A(); }'
+assert text "error: typedef redefinition with different types ('long' vs 'int') [semantic]" 'typedef int T; typedef long T; int main(void) { return 0; }'
+assert text "error: redefinition of 'T' as different kind of symbol [semantic]" 'typedef int T; int main(void) { return 0; } int T;'
+assert text "error: unexpected type name 'T': expected expression [syntax]" 'typedef int T; int main(void) { return T + 1; }'
+assert text "error: non-object type 'int (void)' is not assignable [semantic]" 'int f(void); int g(void); int main(void) { f = g; return 0; }'
+assert text "warning: incompatible pointer types assigning to 'int (*)(int)' from 'int (void)' [semantic]" 'int f(void) { return 0; } int main(void) { int (*p)(int) = f; return 0; }'
+assert text "error: too many arguments to function call, expected 1, have 2 [semantic]" 'typedef int (*fp)(int); int main(void) { fp f; return f(1, 2); }'
+assert text "error: definition of variable with array type needs an explicit size or an initializer [semantic]" 'int main(void) { int a[]; return 0; }'
+assert text "error: invalid argument type 'struct s' to unary expression [semantic]" 'int main(void) { struct s { int a; } x; return !x; }'
+assert text "error: invalid operands to binary expression ('int' and 'struct s') [semantic]" 'int main(void) { struct s { int a; } x; return 1 && x; }'
+assert text "error: invalid operands to binary expression ('int *' and 'int') [semantic]" 'int main(void) { int *p; return p % 2; }'
+assert text "error: invalid operands to binary expression ('int' and 'int *') [semantic]" 'int main(void) { int *p; return 1 << p; }'
+assert text "error: invalid argument type 'int *' to unary expression [semantic]" 'int main(void) { int *p; ~p; return 0; }'
+assert text "error: expression is not assignable [semantic]" 'int main(void) { int a; a + 1 += 2; return 0; }'
+assert text "error: expression is not assignable [semantic]" 'int main(void) { 3++; return 0; }'
+assert text "error: array type 'int[3]' is not assignable [semantic]" 'int main(void) { int a[3]; a += 1; return 0; }'
+assert text "error: array type 'int[3]' is not assignable [semantic]" 'int main(void) { int a[3]; a++; return 0; }'
+assert text "error: invalid operands to binary expression ('int *' and 'int *') [semantic]" 'int main(void) { int *p; p += p; return 0; }'
+assert text "error: invalid operands to binary expression ('int *' and 'int') [semantic]" 'int main(void) { int *p; p *= 2; return 0; }'
+assert text "tmp.c:1:1: error: the number of arguments must be < 7 [codegen]" 'int f(int a, int b, int c, int d, int e, int g, int h) { return h; } int main(void) { return 0; }'
+assert text "error: the number of arguments must be < 7 [codegen]" 'int f(); int main(void) { return f(1, 2, 3, 4, 5, 6, 7); }'
+assert sarif '"version": "2.1.0"' 'int main(void) { return 0 }'
+assert sarif "\"level\": \"error\", \"message\": {\"text\": \"expected ';'\"}" 'int main(void) { return 0 }'
+assert sarif '"insertedContent": {"text": ";"}' 'int main(void) { return 0 }'
+assert sarif "\"description\": {\"text\": \"insert ';'\"}" 'int main(void) { return 0 }'
+assert sarif '"ruleId": "expected-token"' 'int main(void) { return 0 }'
+assert sarif '"columnKind": "unicodeCodePoints"' 'int main(void) { return 0 }'
+# the columns and the offsets are counted in characters, not bytes
+assert sarif '"startLine": 1, "startColumn": 35, "endLine": 1, "endColumn": 35, "charOffset": 34, "charLength": 0' 'int main(void) { /* αβ */ return 0 }'
+assert sarif '"results": []' 'int main(void) { return 0; }'
+
+# the errors of the driver are in the structured output too
+actual=$("${DEBUG}"ccr --diagnostics-format=json "${DEBUG}"no-such-file.c 2>&1)
+pattern='{"severity": "error", "code": "file-not-found", "category": "driver", "message": "couldn'"'"'t open '"${DEBUG}"'no-such-file.c: No such file or directory (os error 2)", "file": null'
+if echo "$actual" | grep -qF -- "$pattern"; then
+    echo -n "."
+else
+    echo -e "\n'$pattern' is expected, but got:\n$actual"
+    exit 1
+fi
+actual=$("${DEBUG}"ccr --diagnostics-format=sarif --bogus 2>&1)
+pattern="\"ruleId\": \"unknown-option\", \"level\": \"error\", \"message\": {\"text\": \"unrecognized command-line option '--bogus'\"}, \"locations\": []"
+if echo "$actual" | grep -qF -- "$pattern"; then
+    echo -n "."
+else
+    echo -e "\n'$pattern' is expected, but got:\n$actual"
+    exit 1
+fi
+
+echo OK
@@ -0,0 +1,94 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+# compile with the options and check the exit status of the executable
+assert() {
+    expected="$1"
+    shift
+
+    "${DEBUG}"ccr -o "${DEBUG}"tmp "$@" > "${DEBUG}"tmp.out 2> "${DEBUG}"tmp.err
+    ${DEBUG}tmp
+    actual="$?"
+
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Options:\n$*"
+        cat "${DEBUG}"tmp.err
+        exit 1
+    fi
+}
+
+# check that the output of the last run contains the pattern
+assert_output() {
+    file="$1"
+    pattern="$2"
+
+    if grep -qF -- "$pattern" "${DEBUG}$file"; then
+        echo -n "."
+    else
+        echo -e "\n'$pattern' is expected in $file, but got:"
+        cat "${DEBUG}$file"
+        exit 1
+    fi
+}
+
+echo 'int main(void) { return 3; }' > "${DEBUG}"tmp.c
+echo 'int abs(int x); int main(void) { return abs(-7); }' > "${DEBUG}"tmp-lib.c
+
+# nothing is printed on success like cc
+assert 3 "${DEBUG}"tmp.c
+if [ -s "${DEBUG}"tmp.out ] || [ -s "${DEBUG}"tmp.err ]; then
+    echo -e "\nno output is expected, but got:"
+    cat "${DEBUG}"tmp.out "${DEBUG}"tmp.err
+    exit 1
+fi
+
+# -v prints the progress to stderr
+assert 3 -v "${DEBUG}"tmp.c
+assert_output tmp.err "Compiling ${DEBUG}tmp.c"
+
+# options of gcc which don't change the output are accepted
+assert 3 -O2 -g -Wall -Wextra -std=c11 -pedantic -fno-common -pipe "${DEBUG}"tmp.c
+assert 3 -I "${DEBUG}" -I"${DEBUG}" "${DEBUG}"tmp.c
+
+# macros are ignored with a warning
+assert 3 -DNDEBUG -D VERSION=2 -UNDEBUG "${DEBUG}"tmp.c
+assert_output tmp.err "ccr: warning: '-DNDEBUG' is ignored since ccr has no preprocessor"
+assert_output tmp.err "ccr: warning: '-DVERSION=2' is ignored since ccr has no preprocessor"
+
+# the libraries are passed to the linker
+assert 7 "${DEBUG}"tmp-lib.c -L"${DEBUG}" -lc -Wl,--as-needed
+
+# errors writing the output are reported, not crashes
+"${DEBUG}"ccr -S -o /dev/full "${DEBUG}"tmp.c 2> "${DEBUG}"tmp.err
+status="$?"
+if [ "$status" != 1 ]; then
+    echo -e "\n1 is expected as the exit status, but got $status"
+    exit 1
+fi
+assert_output tmp.err "couldn't write the assembly: No space left on device"
+
+# unknown options are still errors
+"${DEBUG}"ccr --bogus "${DEBUG}"tmp.c 2> "${DEBUG}"tmp.err
+assert_output tmp.err "ccr: error: unrecognized command-line option '--bogus'"
+
+
+# every input is compiled, and all the errors are reported before failing
+echo 'int f(void) { return 0 }' > "${DEBUG}"tmp-bad1.c
+echo 'int main(void) { return x; }' > "${DEBUG}"tmp-bad2.c
+rm -f "${DEBUG}"tmp-multi
+"${DEBUG}"ccr -o "${DEBUG}"tmp-multi "${DEBUG}"tmp-bad1.c "${DEBUG}"tmp.c "${DEBUG}"tmp-missing.c "${DEBUG}"tmp-bad2.c 2> "${DEBUG}"tmp.err
+status="$?"
+if [ "$status" != 1 ] || [ -e "${DEBUG}"tmp-multi ]; then
+    echo -e "\nthe link is expected to fail with 1, but got $status"
+    exit 1
+fi
+assert_output tmp.err "tmp-bad1.c:1:23: error: expected ';'"
+assert_output tmp.err "ccr: error: couldn't open ${DEBUG}tmp-missing.c"
+assert_output tmp.err "tmp-bad2.c:1:25: error: 'x' is not defined"
+
+echo OK
@@ -0,0 +1,59 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+# implicit values
+assert 0 'int main(void) { enum { A, B, C }; return A; }'
+assert 2 'int main(void) { enum { A, B, C }; return C; }'
+assert 1 'int main(void) { enum { A, B, C, }; return B; }'
+
+# explicit values and constant expressions
+assert 5 'int main(void) { enum { A = 5 }; return A; }'
+assert 11 'int main(void) { enum { A = 10, B }; return B; }'
+assert 4 'int main(void) { enum { A = 2, B = A * 2 }; return B; }'
+assert 9 'int main(void) { enum { A = 2, B = A * 3, C = B + A - 1 }; return C + 2; }'
+assert 255 'int main(void) { enum { A = -1, B }; return (A == -1) * (B == 0) * 255; }'
+assert 7 'int main(void) { enum { A = sizeof(long) - 1 }; return A; }'
+
+# enum types are int
+assert 4 'int main(void) { enum color { RED, GREEN } c; return sizeof(c); }'
+assert 4 'enum color { RED, GREEN }; int main(void) { return sizeof(enum color); }'
+assert 1 'enum color { RED, GREEN }; int main(void) { enum color c = GREEN; return c; }'
+assert 3 'enum state { IDLE, RUN, STOP }; enum state next(enum state s) { return s + 1; } int main(void) { return next(RUN) + 1; }'
+assert 1 'enum state { IDLE, RUN }; enum state g = RUN; int main(void) { return g; }'
+assert 12 'int main(void) { enum { N = 3 }; int a[N * 2]; return sizeof(a) / 2; }'
+
+# scopes
+assert 3 'enum { A = 1 }; int main(void) { enum { A = 3 }; return A; }'
+assert 1 'enum { A = 1 }; int main(void) { { enum { A = 3 }; } return A; }'
+assert 5 'int main(void) { struct { enum { X = 5 } kind; int v; } s; s.kind = X; return s.kind; }'
+
+# the values are folded in the types of the operands
+assert 15 'int main(void) { enum { A = -1u >> 28 }; return A; }'
+assert 0 'int main(void) { enum { A = -1 < 0u }; return A; }'
+assert 1 'int main(void) { enum { A = -2 / 2u >> 30 }; return A; }'
+
+# state machines
+assert 2 'enum state { IDLE, RUN, DONE }; int main(void) { enum state s = IDLE; int n = 0; for (; s != DONE; n = n + 1) { if (s == IDLE) s = RUN; else s = DONE; } return n; }'
+
+echo OK
@@ -8,8 +8,7 @@ assert() {
     input="$2"
 
     echo "$input" > "${DEBUG}"tmp.src
-    "${DEBUG}"ccr -q "${DEBUG}tmp.src"
-    cc -o ${DEBUG}tmp "${DEBUG}"tmp.s
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
     ${DEBUG}tmp
     actual="$?"
     
@@ -26,6 +25,8 @@ assert() {
 assert 30 'int main(void) { return 30; }'
 assert 3 'int ret1(void) { return 1; } int ret2(void) { return 2; } int main(void) { return ret1() + ret2(); } '
 assert 0 'void func(void) { return; } int main(void) { func(); return 0; }'
+assert 4 'void set(int *p) { *p = 4; } int main(void) { int x; set(&x); return x; }'
+assert 0 'int main(void) { int x = 3; x = x + 1; }'
 assert 13 'int fib(int n) {
     if (n == 1) return 1;
     if (n == 2) return 1;
@@ -35,4 +36,11 @@ int main(void) {
     return fib(7);
 }
 '
+assert 7 'int add(int a, int b); int main(void) { return add(3, 4); } int add(int a, int b) { return a + b; }'
+assert 5 'int sub(int, int); int sub(int a, int b); int main(void) { return sub(8, 3); } int sub(int a, int b) { return a - b; }'
+assert 4 'int twice(); int main(void) { return twice(2); } int twice(int x) { return x * 2; }'
+assert 1 'int *id(int *p) { return p; } int main(void) { int a = 1; return *id(&a); }'
+assert 0 'int *null(void) { return 0; } int main(void) { if (null() == 0) return 0; return 1; }'
+assert 6 'int printf(char *fmt, ...); int main(void) { return printf("%d%d%d%d%d\n", 1, 2, 3, 4, 5); }'
+assert 3 'int f(int a, int b, int c) { return c; } int main(void) { return f(1, 2, 7 / 2); }'
 echo OK
@@ -0,0 +1,59 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+assert 0 'int x; int main(void) { return x; }'
+assert 3 'int x; int main(void) { x = 3; return x; }'
+assert 7 'int x = 7; int main(void) { return x; }'
+assert 5 'int x = 2 * 3 - 1; int main(void) { return x; }'
+assert 6 'int x, y = 4; int main(void) { x = 2; return x + y; }'
+assert 2 'int x; int f(void) { x = x + 1; return x; } int main(void) { f(); return f(); }'
+assert 4 'int x = 4; int main(void) { int x = 1; return 3 + x; }'
+assert 120 "char c = 'x'; int main(void) { return c; }"
+assert 111 'char *s = "ok"; int main(void) { return *s; }'
+assert 9 'int x = 9; int *p = &x; int main(void) { return *p; }'
+assert 1 'int x; int x; int x = 1; int main(void) { return x; }'
+assert 3 'extern int x; int main(void) { return x; } int x = 3;'
+assert 5 'int main(void) { extern int y; return y; } int y = 5;'
+assert 7 'int y = 7; int main(void) { int y = 1; { extern int y; return y; } }'
+assert 255 'int x = -1; char c = 255; int main(void) { return (x == -1) * (c == -1) * 255; }'
+
+# the initializers are folded in the types of the operands
+assert 15 'unsigned long g = 0xffffffffffffffff >> 60; int main(void) { return g; }'
+assert 0 'int x = -1 < 0u; int main(void) { return x; }'
+assert 15 'long g = -1u; int main(void) { return g >> 28; }'
+assert 1 'unsigned g = 0x80000000u * 2 + 1; int main(void) { return g; }'
+
+# globals shared with an object built by another compiler
+echo 'int shared = 10; int get(void) { return shared; }' > "${DEBUG}"tmp-shared.c
+cc -c -o "${DEBUG}"tmp-shared.o "${DEBUG}"tmp-shared.c
+echo 'extern int shared; int get(void); int main(void) { shared = shared + 5; return get(); }' > "${DEBUG}"tmp.c
+"${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.c "${DEBUG}"tmp-shared.o
+${DEBUG}tmp
+actual="$?"
+if [ "$actual" != 15 ]; then
+    echo -e "\n15 is expected, but got $actual"
+    exit 1
+fi
+
+echo OK
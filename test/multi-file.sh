@@ -0,0 +1,40 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+# compile each input with ccr and link them into one executable
+assert() {
+    expected="$1"
+    shift
+
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "$@"
+    ${DEBUG}tmp
+    actual="$?"
+
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$*"
+        exit 1
+    fi
+}
+
+echo 'int add(int a, int b); int sub(int a, int b); int main(void) { return add(3, 4) + sub(10, 2); }' > "${DEBUG}"tmp-main.c
+echo 'int add(int a, int b) { return a + b; }' > "${DEBUG}"tmp-add.c
+echo 'int sub(int a, int b) { return a - b; }' > "${DEBUG}"tmp-sub.c
+
+# C sources only
+assert 15 "${DEBUG}"tmp-main.c "${DEBUG}"tmp-add.c "${DEBUG}"tmp-sub.c
+
+# mix of a C source, an assembly and an object file
+"${DEBUG}"ccr -q -S -o "${DEBUG}"tmp-add.s "${DEBUG}"tmp-add.c
+"${DEBUG}"ccr -q -c -o "${DEBUG}"tmp-sub.o "${DEBUG}"tmp-sub.c
+assert 15 "${DEBUG}"tmp-main.c "${DEBUG}"tmp-add.s "${DEBUG}"tmp-sub.o
+
+# an object file built by another compiler
+cc -c -o "${DEBUG}"tmp-link.o "${TEST_DIR}"link-test/link.c
+assert 13 "${TEST_DIR}"link-test/test.c "${DEBUG}"tmp-link.o
+
+echo OK
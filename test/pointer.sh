@@ -8,8 +8,7 @@ assert() {
     input="$2"
 
     echo "$input" > "${DEBUG}"tmp.src
-    "${DEBUG}"ccr -q "${DEBUG}tmp.src"
-    cc -o ${DEBUG}tmp "${DEBUG}"tmp.s
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
     ${DEBUG}tmp
     actual="$?"
     
@@ -24,9 +23,21 @@ assert() {
 }
 
 assert 10 'int main(void) {int a = 10;return *(&a);}'
-assert 2 'int main(void) {int a = 1;int b = 2;return *(&a-8);}'
-assert 6 'int main(void) {int a = 5;int b;*(&a-8)=6;return *&b;}'
+assert 2 'int main(void) {int a = 1;int b = 2;return *(&a-1);}'
+assert 6 'int main(void) {int a = 5;int b;*(&a-1)=6;return *&b;}'
 assert 200 'int main(void) {int a = 100; int *b = &a; *b = 200; return a;}'
 assert 200 'int main(void) {int a = 200, *p, **pp; p = &a; pp = &p; return **pp; }'
 
+# pointer arithmetic is scaled by the size of the pointee
+assert 3 'int main(void) { int a[4]; a[0] = 1; a[1] = 2; a[2] = 3; int *p = a; return *(p + 2); }'
+assert 3 'int main(void) { int a[4]; a[2] = 3; return *(2 + a); }'
+assert 2 'int main(void) { int a[4]; a[1] = 2; int *p = a + 3; return *(p - 2); }'
+assert 3 'int main(void) { int a[4]; int *p = a + 1; int *q = a + 4; return q - p; }'
+assert 5 'int main(void) { char s[8]; return &s[7] - &s[2]; }'
+assert 2 'int main(void) { long a[3]; long *p = a; p = p + 2; return p - a; }'
+assert 7 'int main(void) { int m[3][4]; m[1][3] = 7; return *(*(m + 1) + 3); }'
+assert 8 'int main(void) { int *p; int *q = p + 1; return (q - p) * sizeof(*p) + 4; }'
+assert 1 'int main(void) { int a[2]; return (a + 1) - a; }'
+assert 8 'int main(void) { int a[2]; int *p = a; int *q = a + 2; return sizeof(q - p); }'
+
 echo OK
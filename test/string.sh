@@ -0,0 +1,43 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+# character literals
+assert 97 "int main(void) { return 'a'; }"
+assert 10 "int main(void) { return '\\n'; }"
+assert 65 "int main(void) { return '\\x41'; }"
+assert 63 "int main(void) { return '\\077'; }"
+assert 39 "int main(void) { return '\\''; }"
+assert 1 "int main(void) { return '\\xff' == -1; }"
+
+# string literals
+assert 104 'int main(void) { return *"hi"; }'
+assert 105 'int main(void) { return *("hi" + 1); }'
+assert 0 'int main(void) { return *("hi" + 2); }'
+assert 99 'int main(void) { char *s = "ab" "cd"; return *(s + 2); }'
+assert 9 'int main(void) { return *"\t"; }'
+assert 3 'int main(void) { char *s = "abc"; char c = *(s + 2); return c - 96; }'
+assert 0 'int puts(char *s); int main(void) { puts("hello, world"); return 0; }'
+assert 5 'int printf(char *fmt, ...); int main(void) { return 1 + printf("%d %d\n", 1, 2); }'
+
+echo OK
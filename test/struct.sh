@@ -0,0 +1,85 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+# members
+assert 3 'int main(void) { struct { int a; int b; } x; x.a = 1; x.b = 2; return x.a + x.b; }'
+assert 7 'struct point { int x, y; }; int main(void) { struct point p; p.y = 7; return p.y; }'
+assert 5 'int main(void) { struct { char c; int a[3]; } x; x.a[2] = 5; return x.a[2]; }'
+assert 6 'int main(void) { struct { struct { int a; int b; } in; int c; } x; x.in.b = 6; return x.in.b; }'
+assert 9 'int main(void) { struct { int a; } x[3]; x[1].a = 9; return x[1].a; }'
+assert 2 'int main(void) { struct { int a; int b; } x; x.a = 1; x.b = 2; int *p = &x.b; return *p; }'
+
+# layout
+assert 8 'int main(void) { struct { int a; int b; } x; return sizeof(x); }'
+assert 8 'int main(void) { struct { char c; int a; } x; return sizeof(x); }'
+assert 16 'int main(void) { struct { char c; long l; } x; return sizeof(x); }'
+assert 8 'int main(void) { struct { int a; char c; } x[1]; return sizeof(x); }'
+assert 5 'int main(void) { struct { char a; int b; char c; } x; x.a = 1; x.b = 2; x.c = 3; return x.b + x.c; }'
+assert 6 'int main(void) { struct { char a; short b; char c; } x; return sizeof(x); }'
+assert 2 'int main(void) { struct { char a; short b; } x; return _Alignof(struct { char a; short b; }); }'
+assert 0 'int main(void) { struct {} x; return sizeof(x); }'
+
+# unions
+assert 8 'int main(void) { union { int a; long b; char c[3]; } x; return sizeof(x); }'
+assert 4 'int main(void) { union { char c[5]; int a; } x; return _Alignof(union { char c[5]; int a; }); }'
+assert 8 'int main(void) { union { char c[5]; int a; } x; return sizeof(x); }'
+assert 15 'int main(void) { union { int a; char b; } x; x.a = 271; return x.b; }'
+assert 3 'int main(void) { union { int a; char b[4]; } x; x.a = 0x01020304; return x.b[1]; }'
+
+# anonymous members
+assert 7 'int main(void) { struct { int a; struct { int b; int c; }; } x; x.c = 7; return x.c; }'
+assert 12 'int main(void) { struct { int a; struct { int b; int c; }; } x; return sizeof(x); }'
+assert 1 'int main(void) { struct { int tag; union { int i; char c; }; } x; x.tag = 1; x.i = 2; return x.tag; }'
+assert 4 'int main(void) { struct { int tag; union { int i; char c; }; } x; x.c = 4; return x.i; }'
+
+# tags
+assert 8 'int main(void) { struct t { int a; int b; }; struct t x; return sizeof(x); }'
+assert 8 'int main(void) { struct t { int a; int b; }; { struct t { char a; } y; } struct t x; return sizeof(x); }'
+assert 1 'int main(void) { struct t { int a; int b; }; { struct t { char a; } y; return sizeof(y); } }'
+assert 4 'struct t { int a; }; int main(void) { struct t x; x.a = 4; return x.a; }'
+assert 3 'struct t { int a; }; struct t g; int main(void) { g.a = 3; return g.a; }'
+assert 16 'int main(void) { struct node { int v; struct node *next; }; return sizeof(struct node); }'
+
+# pointers to structs
+
+assert 8 'int main(void) { struct t { int a; int b; } x; struct t *p = &x; p->b = 8; return x.b; }'
+assert 3 'int main(void) { struct t { int a; int b; } x; struct t *p = &x; x.a = 3; return (*p).a; }'
+assert 2 'int main(void) { struct t { int a; int b; } x[3]; struct t *p = x + 1; p->a = 2; return x[1].a; }'
+assert 24 'int main(void) { struct t { int a; int b; } x[4]; return (&x[3] - &x[0]) * sizeof(struct t); }'
+assert 9 'int main(void) { struct node { int v; struct node *next; } a, b; a.next = &b; b.v = 9; return a.next->v; }'
+assert 6 'struct s; struct s *p; struct s { int a; int b; }; int main(void) { struct s x; p = &x; p->b = 6; return x.b; }'
+
+# assignment copies the struct
+assert 3 'int main(void) { struct t { int a; char b; } x, y; x.a = 3; x.b = 4; y = x; return y.a; }'
+assert 4 'int main(void) { struct t { int a; char b; } x, y; x.a = 3; x.b = 4; y = x; return y.b; }'
+assert 1 'int main(void) { struct t { int a; } x, y; x.a = 1; y.a = 2; y = x; x.a = 5; return y.a; }'
+assert 7 'int main(void) { union u { int a; char b; } x, y; x.a = 7; y = x; return y.a; }'
+assert 5 'int main(void) { struct t { int a[3]; } x, y, z; x.a[2] = 5; z = y = x; return z.a[2]; }'
+assert 6 'struct t { int a; long b; } g; int main(void) { struct t x; x.b = 6; g = x; return g.b; }'
+
+# functions taking and returning pointers to structs
+assert 4 'struct t { int a; int b; }; struct t g; struct t *f(void) { g.b = 4; return &g; } int main(void) { return f()->b; }'
+assert 7 'struct t { int a; int b; }; int get(struct t *p) { return p->a + p->b; } int main(void) { struct t x; x.a = 3; x.b = 4; return get(&x); }'
+
+echo OK
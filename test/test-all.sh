@@ -3,7 +3,17 @@ TEST_DIR="$(cd $(dirname $0); pwd)/"
 
 ${TEST_DIR}culc.sh
 ${TEST_DIR}pointer.sh
+${TEST_DIR}type.sh
 ${TEST_DIR}control-syntax.sh
 ${TEST_DIR}func.sh
+${TEST_DIR}string.sh
+${TEST_DIR}array.sh
+${TEST_DIR}struct.sh
+${TEST_DIR}enum.sh
+${TEST_DIR}typedef.sh
+${TEST_DIR}global.sh
+${TEST_DIR}multi-file.sh
+${TEST_DIR}driver.sh
+${TEST_DIR}diagnostics.sh
 
 echo "Test finished"
\ No newline at end of file
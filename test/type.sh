@@ -0,0 +1,95 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+# sizes and signedness
+assert 0 'int main(void) { char c = 255; return c + 1; }'
+assert 255 'int main(void) { unsigned char c = 255; return c; }'
+assert 0 'int main(void) { signed char c = 128; return c + 128; }'
+assert 1 'int main(void) { short s = 65535; return s == -1; }'
+assert 1 'int main(void) { unsigned short s = 65535; return s == 65535; }'
+assert 1 'int main(void) { short int s = 32767; long int l = 2147483648; return (s == 32767) * (l == 2147483648); }'
+assert 1 'int main(void) { long long x = 4294967296; return x / 4294967296; }'
+assert 1 'int main(void) { unsigned u = 4294967295; return u == 4294967295; }'
+assert 1 'int main(void) { long unsigned int x = 8589934592; return x == 8589934592; }'
+assert 1 'int main(void) { int i = 4294967297; return i; }'
+
+# sizeof and _Alignof
+assert 4 'int main(void) { return sizeof(int); }'
+assert 1 'int main(void) { return sizeof(char); }'
+assert 2 'int main(void) { return sizeof(unsigned short int); }'
+assert 8 'int main(void) { return sizeof(long long); }'
+assert 8 'int main(void) { return sizeof(char *); }'
+assert 48 'int main(void) { return sizeof(int[3][4]); }'
+assert 4 'int main(void) { int x; return sizeof x; }'
+assert 8 'int main(void) { int *p; return sizeof(p); }'
+assert 40 'int main(void) { int a[10]; return sizeof a; }'
+assert 16 'int main(void) { int m[3][4]; return sizeof m[0]; }'
+assert 10 'int main(void) { int a[10]; return sizeof(a) / sizeof(a[0]); }'
+assert 4 'int main(void) { return sizeof "abc"; }'
+assert 4 'int main(void) { char c; return sizeof(c + 1); }'
+assert 8 'int main(void) { return sizeof sizeof(int); }'
+assert 3 'int main(void) { int x = 3; sizeof(x = 5); return x; }'
+assert 28 'int g[7]; int main(void) { return sizeof g; }'
+assert 8 'int main(void) { return _Alignof(long); }'
+assert 2 'int main(void) { return _Alignof(short[5]); }'
+assert 8 'int main(void) { return sizeof(int (*)(int)); }'
+assert 8 'int main(void) { return _Alignof(char (*)(void)); }'
+assert 32 'int main(void) { return sizeof(int *[4]) + sizeof(int (*)[4]) - 8; }'
+assert 8 'int main(void) { return sizeof(int (**)(int, char *)); }'
+
+# integer promotions and usual arithmetic conversions
+assert 1 'int main(void) { unsigned u = 0; return u - 1 > 0; }'
+assert 0 'int main(void) { unsigned u = 1; int i = -1; return i < u; }'
+assert 1 'int main(void) { long l = -1; int i = 0; return l < i; }'
+assert 1 'int main(void) { unsigned u = 4294967295; u = u + 1; return u == 0; }'
+assert 44 'int main(void) { char c; return c = 300; }'
+assert 144 'int main(void) { unsigned char a = 200; unsigned char b = 200; return (a + b) - 256; }'
+assert 1 'int main(void) { unsigned u = 4294967295; return u / 2 == 2147483647; }'
+assert 253 'int main(void) { return -7 / 2; }'
+assert 4 'int main(void) { char c; return sizeof(c + c); }'
+assert 8 'int main(void) { unsigned u; long l; return sizeof(u + l); }'
+assert 4 'int main(void) { unsigned short s; return sizeof(s * 2); }'
+assert 1 'int main(void) { unsigned long a = 1; long b = -1; return b > a; }'
+assert 1 'char f(void) { return 257; } int main(void) { return f(); }'
+
+# the narrow stores don't clobber the neighbors
+assert 3 'int main(void) { char a = 1; char b = 2; short c = 0; int d = 0; a = 3; c = 4; return a; }'
+assert 10 'int main(void) { char a[4]; int x = 7; a[0] = 1; a[1] = 1; a[2] = 1; a[3] = 0; return x + a[0] + a[1] + a[2]; }'
+assert 6 'int main(void) { short s[3]; s[0] = 1; s[1] = 2; s[2] = 3; return s[0] + s[1] + s[2]; }'
+assert 11 'int f(char a, short b, int c, long d) { return a + b + c + d; } int main(void) { return f(1, 2, 3, 5); }'
+assert 1 'char c(void) { return 511; } int main(void) { return c() == -1; }'
+
+# narrow values passed to and returned from an object built by another compiler
+echo 'signed char neg(signed char c) { return -c; } unsigned short add(unsigned short a, short b) { return a + b; }' > "${DEBUG}"tmp-abi.c
+cc -c -o "${DEBUG}"tmp-abi.o "${DEBUG}"tmp-abi.c
+echo 'char neg(char c); unsigned short add(unsigned short a, short b); int main(void) { return (neg(5) == -5) + (add(65535, 2) == 1) * 2; }' > "${DEBUG}"tmp.c
+"${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.c "${DEBUG}"tmp-abi.o
+${DEBUG}tmp
+actual="$?"
+if [ "$actual" != 3 ]; then
+    echo -e "\n3 is expected, but got $actual"
+    exit 1
+fi
+
+echo OK
@@ -0,0 +1,70 @@
+#!/bin/bash
+TEST_DIR="$(cd $(dirname $0); pwd)/"
+CCR_DIR="${TEST_DIR}../"
+DEBUG="${CCR_DIR}target/debug/"
+
+assert() {
+    expected="$1"
+    input="$2"
+
+    echo "$input" > "${DEBUG}"tmp.src
+    "${DEBUG}"ccr -q -o "${DEBUG}"tmp "${DEBUG}"tmp.src
+    ${DEBUG}tmp
+    actual="$?"
+    
+    if [ "$actual" = "$expected" ]; then
+        echo -n "."
+        #echo "Got $actual as expected"
+    else
+        echo -e "\n$expected is expected, but got $actual"
+        echo -e "Input:\n$input"
+        exit 1
+    fi
+}
+
+# basic types
+assert 3 'typedef int T; int main(void) { T x = 3; return x; }'
+assert 8 'typedef long L; int main(void) { L x; return sizeof(x); }'
+assert 2 'int main(void) { typedef short S; return sizeof(S); }'
+assert 5 'typedef int A, B; int main(void) { A a = 2; B b = 3; return a + b; }'
+assert 4 'typedef int T; typedef T U; int main(void) { U x = 4; return x; }'
+assert 1 'typedef int T; typedef int T; int main(void) { T x = 1; return x; }'
+assert 255 'typedef unsigned char byte; int main(void) { byte b = 255; return b; }'
+
+# T * x is a declaration when T is a type, and a multiplication otherwise
+assert 7 'typedef int T; int main(void) { T a = 7; T * x = &a; return *x; }'
+assert 6 'int main(void) { int T = 2; int x = 3; T * x; return T * x; }'
+assert 6 'typedef int T; int main(void) { int T = 2; int x = 3; T * x; return T * x; }'
+assert 9 'typedef int T; int main(void) { { int T = 3; T * T; } T x = 9; return x; }'
+assert 4 'typedef int T; int main(void) { int y = 2; { T * y; } return y * 2; }'
+
+# pointers and arrays
+assert 8 'typedef int *P; int main(void) { int a = 8; P p = &a; return *p; }'
+assert 12 'typedef int A3[3]; int main(void) { A3 a; return sizeof(a); }'
+assert 6 'typedef int A3[3]; int main(void) { A3 a; a[0] = 1; a[2] = 5; return a[0] + a[2]; }'
+assert 24 'typedef int A3[3]; int main(void) { A3 m[2]; return sizeof(m); }'
+assert 3 'typedef char *str; int main(void) { str s = "abc"; return *(s + 2) - 96; }'
+
+# structs, unions and enums
+assert 3 'typedef struct { int x, y; } Point; int main(void) { Point p; p.x = 1; p.y = 2; return p.x + p.y; }'
+assert 5 'typedef struct node Node; struct node { int v; Node *next; }; int main(void) { Node a, b; a.next = &b; b.v = 5; return a.next->v; }'
+assert 4 'typedef struct point { int x; } point; int main(void) { point p; struct point *q = &p; q->x = 4; return p.x; }'
+assert 8 'typedef union { int i; long l; } U; int main(void) { return sizeof(U); }'
+assert 2 'typedef enum { IDLE, RUN, STOP } state; int main(void) { state s = STOP; return s; }'
+
+# function types and function pointers
+assert 5 'typedef int (*binop)(int, int); int add(int a, int b) { return a + b; } int main(void) { binop f = add; return f(2, 3); }'
+assert 6 'typedef int (*binop)(int, int); int mul(int a, int b) { return a * b; } int main(void) { binop f = &mul; return (*f)(2, 3); }'
+assert 1 'typedef int (*binop)(int, int); int sub(int a, int b) { return a - b; } int apply(binop f, int a, int b) { return f(a, b); } int main(void) { return apply(sub, 3, 2); }'
+assert 9 'typedef int F(int); F twice; int twice(int x) { return x * 2; } int main(void) { F *f = twice; return f(4) + 1; }'
+assert 8 'typedef int (*fp)(void); int main(void) { fp f; return sizeof(f); }'
+assert 7 'int seven(void) { return 7; } int (*g)(void) = seven; int main(void) { return g(); }'
+assert 4 'int one(void) { return 1; } int three(void) { return 3; } int main(void) { int (*fs[2])(void); fs[0] = one; fs[1] = three; return fs[0]() + fs[1](); }'
+assert 10 'typedef struct { int (*op)(int, int); } Ops; int add(int a, int b) { return a + b; } int main(void) { Ops o; o.op = add; return o.op(4, 6); }'
+assert 0 'int puts(char *s); typedef int (*printer)(char *); int main(void) { printer p = puts; p("via pointer"); return 0; }'
+
+# typedef names in function declarations
+assert 3 'typedef int T; T f(T x) { return x + 1; } int main(void) { return f(2); }'
+assert 4 'typedef struct { int a; } S; S *get(S *p) { return p; } int main(void) { S s; s.a = 4; return get(&s)->a; }'
+
+echo OK